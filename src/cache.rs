@@ -0,0 +1,426 @@
+//! Remembering directory listings, detected types and hashes between runs.
+//!
+//! Each scanned tree gets its own cache file. A directory's listing is
+//! reused while the directory's modification time is unchanged, and a
+//! file's detected type or hash while its size and modification time are.
+
+use std::collections::HashMap;
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, SystemTime};
+
+use serde::{Deserialize, Serialize};
+
+use crate::detect::{self, ContentType};
+
+/// Version of the cache format written by this build. Caches written by any
+/// other version are discarded. Raised whenever detection changes, so that
+/// types cached by an earlier build are detected again.
+pub const VERSION: u32 = 2;
+
+/// Anything modified this recently is not cached: on file systems with
+/// coarse timestamps, a further change could keep the same modification
+/// time and go unnoticed.
+const RACY_WINDOW: Duration = Duration::from_secs(2);
+
+/// The default cache directory: `$XDG_CACHE_HOME/fext`, falling back to
+/// `~/.cache/fext` (or `%LOCALAPPDATA%\fext` on Windows).
+pub fn default_dir() -> Option<PathBuf> {
+    let cache_dir = env::var_os("XDG_CACHE_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".cache")))
+        .or_else(|| env::var_os("LOCALAPPDATA").map(PathBuf::from))?;
+
+    Some(cache_dir.join("fext"))
+}
+
+/// A name in a directory listing.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct Entry {
+    #[serde(with = "crate::encoding::path")]
+    pub(crate) name: PathBuf,
+    pub(crate) is_dir: bool,
+}
+
+/// The listing of a directory, relative to the root.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CachedDir {
+    #[serde(with = "crate::encoding::path")]
+    path: PathBuf,
+    modified: SystemTime,
+    entries: Vec<Entry>,
+}
+
+/// What is known about a file's content, relative to the root.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CachedFile {
+    #[serde(with = "crate::encoding::path")]
+    path: PathBuf,
+    size: u64,
+    modified: SystemTime,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    detected: Option<Detection>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    hash: Option<String>,
+}
+
+/// The outcome of content detection, which may have found nothing.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Detection {
+    content_type: Option<String>,
+}
+
+/// The cache as stored on disk, in path order.
+#[derive(Debug, Serialize, Deserialize)]
+struct Stored {
+    version: u32,
+    #[serde(with = "crate::encoding::path")]
+    root: PathBuf,
+    dirs: Vec<CachedDir>,
+    files: Vec<CachedFile>,
+}
+
+impl Stored {
+    /// Reads the cache file at `path`.
+    fn load(path: &Path) -> io::Result<Self> {
+        let stored: Self = serde_json::from_reader(BufReader::new(File::open(path)?))?;
+        if stored.version != VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unsupported cache version {}", stored.version),
+            ));
+        }
+        Ok(stored)
+    }
+
+    /// Replaces the cache file at `path`, writing a temporary file first so
+    /// that an interrupted write never leaves a truncated cache behind.
+    fn save(&self, path: &Path) -> io::Result<()> {
+        let temporary = path.with_extension(format!("{}.tmp", process::id()));
+        let written = File::create(&temporary).and_then(|file| {
+            let mut out = BufWriter::new(file);
+            serde_json::to_writer(&mut out, self)?;
+            out.flush()
+        });
+        match written.and_then(|()| fs::rename(&temporary, path)) {
+            Ok(()) => Ok(()),
+            Err(e) => {
+                let _ = fs::remove_file(&temporary);
+                Err(e)
+            }
+        }
+    }
+}
+
+/// The cached listings, detected types and hashes of one tree. Lookups and
+/// updates take `&self`, so one cache can serve a parallel walk; updates
+/// reach the disk on [`Cache::save`].
+#[derive(Debug)]
+pub struct Cache {
+    path: PathBuf,
+    root: PathBuf,
+    dirs: Mutex<HashMap<PathBuf, CachedDir>>,
+    files: Mutex<HashMap<PathBuf, CachedFile>>,
+    changed: AtomicBool,
+}
+
+impl Cache {
+    /// Opens the cache of the tree at `root` kept in `dir`. A cache that is
+    /// missing, unreadable or from another version starts out empty.
+    pub fn open(dir: impl AsRef<Path>, root: impl AsRef<Path>) -> io::Result<Self> {
+        let root = fs::canonicalize(root)?;
+        let path = dir.as_ref().join(file_name(&root));
+
+        let (dirs, files) = match Stored::load(&path) {
+            Ok(stored) if stored.root == root => (
+                stored
+                    .dirs
+                    .into_iter()
+                    .map(|dir| (dir.path.clone(), dir))
+                    .collect(),
+                stored
+                    .files
+                    .into_iter()
+                    .map(|file| (file.path.clone(), file))
+                    .collect(),
+            ),
+            _ => (HashMap::new(), HashMap::new()),
+        };
+
+        Ok(Self {
+            path,
+            root,
+            dirs: Mutex::new(dirs),
+            files: Mutex::new(files),
+            changed: AtomicBool::new(false),
+        })
+    }
+
+    /// The canonical root of the cached tree.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Writes the cache to disk if anything was stored since it was opened,
+    /// creating the cache directory if needed.
+    pub fn save(&self) -> io::Result<()> {
+        if !self.changed.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+
+        let mut dirs: Vec<CachedDir> = lock(&self.dirs).values().cloned().collect();
+        dirs.sort_by(|a, b| a.path.as_os_str().cmp(b.path.as_os_str()));
+        let mut files: Vec<CachedFile> = lock(&self.files).values().cloned().collect();
+        files.sort_by(|a, b| a.path.as_os_str().cmp(b.path.as_os_str()));
+
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        Stored {
+            version: VERSION,
+            root: self.root.clone(),
+            dirs,
+            files,
+        }
+        .save(&self.path)?;
+        self.changed.store(false, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// The listing of the directory at `relative`, if it was cached when the
+    /// directory had the modification time `modified`.
+    pub(crate) fn listing(
+        &self,
+        relative: &Path,
+        modified: Option<SystemTime>,
+    ) -> Option<Vec<Entry>> {
+        let modified = modified?;
+        lock(&self.dirs)
+            .get(relative)
+            .filter(|dir| dir.modified == modified)
+            .map(|dir| dir.entries.clone())
+    }
+
+    /// Remembers the listing of the directory at `relative`, read while it
+    /// had the modification time `modified`.
+    pub(crate) fn store_listing(
+        &self,
+        relative: &Path,
+        modified: Option<SystemTime>,
+        entries: Vec<Entry>,
+    ) {
+        let Some(modified) = modified.filter(|&modified| is_settled(modified)) else {
+            return;
+        };
+        let dir = CachedDir {
+            path: relative.to_path_buf(),
+            modified,
+            entries,
+        };
+        lock(&self.dirs).insert(dir.path.clone(), dir);
+        self.changed.store(true, Ordering::Relaxed);
+    }
+
+    /// The type detected in the file at `relative` (`Some(None)` if none
+    /// was), if it was cached when the file had this size and modification
+    /// time.
+    pub(crate) fn detected(
+        &self,
+        relative: &Path,
+        size: u64,
+        modified: Option<SystemTime>,
+    ) -> Option<Option<&'static ContentType>> {
+        let files = lock(&self.files);
+        let detection = current(&files, relative, size, modified)?
+            .detected
+            .as_ref()?;
+        match &detection.content_type {
+            None => Some(None),
+            // A type this build no longer knows is detected again.
+            Some(name) => detect::by_name(name).map(Some),
+        }
+    }
+
+    /// Remembers the type detected in the file at `relative`.
+    pub(crate) fn store_detected(
+        &self,
+        relative: &Path,
+        size: u64,
+        modified: Option<SystemTime>,
+        detected: Option<&'static ContentType>,
+    ) {
+        self.update_file(relative, size, modified, |file| {
+            file.detected = Some(Detection {
+                content_type: detected.map(|content_type| content_type.name.to_string()),
+            });
+        });
+    }
+
+    /// The hash of the file at `relative`, if it was cached when the file
+    /// had this size and modification time.
+    pub(crate) fn hash(
+        &self,
+        relative: &Path,
+        size: u64,
+        modified: Option<SystemTime>,
+    ) -> Option<String> {
+        let files = lock(&self.files);
+        current(&files, relative, size, modified)?.hash.clone()
+    }
+
+    /// Remembers the hash of the file at `relative`.
+    pub(crate) fn store_hash(
+        &self,
+        relative: &Path,
+        size: u64,
+        modified: Option<SystemTime>,
+        hash: String,
+    ) {
+        self.update_file(relative, size, modified, |file| file.hash = Some(hash));
+    }
+
+    /// Applies `update` to the cached file at `relative`, first forgetting
+    /// everything known about it if it has changed since.
+    fn update_file(
+        &self,
+        relative: &Path,
+        size: u64,
+        modified: Option<SystemTime>,
+        update: impl FnOnce(&mut CachedFile),
+    ) {
+        let Some(modified) = modified.filter(|&modified| is_settled(modified)) else {
+            return;
+        };
+        let mut files = lock(&self.files);
+        let file = files
+            .entry(relative.to_path_buf())
+            .or_insert_with(|| CachedFile {
+                path: relative.to_path_buf(),
+                size,
+                modified,
+                detected: None,
+                hash: None,
+            });
+        if file.size != size || file.modified != modified {
+            file.size = size;
+            file.modified = modified;
+            file.detected = None;
+            file.hash = None;
+        }
+        update(file);
+        self.changed.store(true, Ordering::Relaxed);
+    }
+}
+
+/// What [`prune`] removed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Pruned {
+    /// Cache files deleted because their tree is gone, they were left
+    /// empty, or they could not be read.
+    pub caches: usize,
+    /// Directories and files dropped from the caches because they changed
+    /// or no longer exist.
+    pub entries: usize,
+    /// Bytes freed on disk.
+    pub bytes: u64,
+}
+
+/// Drops everything from the caches in `dir` that could no longer be used:
+/// directories and files that changed or no longer exist, and whole caches
+/// of trees that are gone.
+pub fn prune(dir: impl AsRef<Path>) -> io::Result<Pruned> {
+    let mut pruned = Pruned::default();
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(pruned),
+        Err(e) => return Err(e),
+    };
+
+    for entry in entries {
+        let path = entry?.path();
+        if path.extension().is_none_or(|extension| extension != "json") {
+            continue;
+        }
+        let before = fs::metadata(&path)?.len();
+
+        let mut stored = match Stored::load(&path) {
+            Ok(stored) if stored.root.is_dir() => stored,
+            _ => {
+                fs::remove_file(&path)?;
+                pruned.caches += 1;
+                pruned.bytes += before;
+                continue;
+            }
+        };
+
+        let count = stored.dirs.len() + stored.files.len();
+        stored.dirs.retain(|dir| {
+            fs::metadata(stored.root.join(&dir.path))
+                .and_then(|metadata| metadata.modified())
+                .is_ok_and(|modified| modified == dir.modified)
+        });
+        stored.files.retain(|file| {
+            fs::metadata(stored.root.join(&file.path)).is_ok_and(|metadata| {
+                metadata.is_file()
+                    && metadata.len() == file.size
+                    && metadata
+                        .modified()
+                        .is_ok_and(|modified| modified == file.modified)
+            })
+        });
+        let removed = count - stored.dirs.len() - stored.files.len();
+        pruned.entries += removed;
+
+        if stored.dirs.is_empty() && stored.files.is_empty() {
+            fs::remove_file(&path)?;
+            pruned.caches += 1;
+            pruned.bytes += before;
+        } else if removed > 0 {
+            stored.save(&path)?;
+            pruned.bytes += before.saturating_sub(fs::metadata(&path)?.len());
+        }
+    }
+
+    Ok(pruned)
+}
+
+/// The cache file of the tree at the canonical path `root`.
+fn file_name(root: &Path) -> String {
+    let hash = blake3::hash(root.as_os_str().as_encoded_bytes());
+    format!("{}.json", &hash.to_hex()[..16])
+}
+
+/// The cached file at `relative`, if it still has this size and
+/// modification time.
+fn current<'a>(
+    files: &'a HashMap<PathBuf, CachedFile>,
+    relative: &Path,
+    size: u64,
+    modified: Option<SystemTime>,
+) -> Option<&'a CachedFile> {
+    let modified = modified?;
+    files
+        .get(relative)
+        .filter(|file| file.size == size && file.modified == modified)
+}
+
+/// Whether `modified` lies far enough in the past to be trusted.
+fn is_settled(modified: SystemTime) -> bool {
+    SystemTime::now()
+        .duration_since(modified)
+        .is_ok_and(|age| age >= RACY_WINDOW)
+}
+
+/// Locks `mutex`, carrying on with the data if another thread panicked
+/// while holding it.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
@@ -0,0 +1,57 @@
+//! Finding extensions written in more than one casing.
+
+use std::collections::BTreeMap;
+
+use crate::encoding;
+use crate::{ExtensionIndex, FileEntry};
+
+/// An extension that appears in more than one casing, such as "jpg" and
+/// "JPG".
+#[derive(Debug, Clone)]
+pub struct CaseVariants<'a> {
+    /// The extension in lowercase.
+    pub extension: String,
+    /// Each casing as written, with the files using it in path order.
+    pub variants: BTreeMap<String, Vec<&'a FileEntry>>,
+}
+
+impl<'a> CaseVariants<'a> {
+    /// Collects the extensions in `index` that appear in more than one
+    /// casing, in order of their lowercased form. Only the last part of a
+    /// file name's extension is compared, whichever key it was grouped under.
+    pub fn find(index: &'a ExtensionIndex) -> Vec<Self> {
+        let mut by_extension: BTreeMap<String, BTreeMap<String, Vec<&FileEntry>>> = BTreeMap::new();
+        for file in index.files() {
+            let Some(extension) = file.path().extension() else {
+                continue;
+            };
+            let written = encoding::escape(extension).into_owned();
+            by_extension
+                .entry(written.to_lowercase())
+                .or_default()
+                .entry(written)
+                .or_default()
+                .push(file);
+        }
+
+        by_extension
+            .into_iter()
+            .filter(|(_, variants)| variants.len() > 1)
+            .map(|(extension, mut variants)| {
+                // Files of one casing may come from several groups.
+                for files in variants.values_mut() {
+                    files.sort_by(|a, b| a.path().as_os_str().cmp(b.path().as_os_str()));
+                }
+                Self {
+                    extension,
+                    variants,
+                }
+            })
+            .collect()
+    }
+
+    /// Total number of files across all casings.
+    pub fn file_count(&self) -> usize {
+        self.variants.values().map(Vec::len).sum()
+    }
+}
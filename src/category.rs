@@ -0,0 +1,198 @@
+//! Higher-level categories (images, documents, code, ...) over extensions.
+
+use std::collections::{BTreeMap, HashMap};
+
+use crate::extension::normalize;
+use crate::{ExtensionIndex, FileEntry};
+
+/// Category for extensions the taxonomy does not know.
+pub const OTHER: &str = "other";
+
+/// The built-in taxonomy, as `(category, extensions)` pairs.
+const BUILTIN: &[(&str, &[&str])] = &[
+    (
+        "images",
+        &[
+            "jpg", "jpeg", "jpe", "jfif", "png", "gif", "webp", "bmp", "tif", "tiff", "svg",
+            "svgz", "ico", "heic", "heif", "avif", "psd", "raw", "dng", "nef", "cr2", "arw", "xcf",
+        ],
+    ),
+    (
+        "documents",
+        &[
+            "pdf", "doc", "docx", "odt", "rtf", "txt", "md", "rst", "tex", "epub", "xls", "xlsx",
+            "ods", "csv", "tsv", "ppt", "pptx", "odp", "pages", "numbers", "key",
+        ],
+    ),
+    (
+        "code",
+        &[
+            "rs", "py", "go", "c", "h", "cc", "cpp", "cxx", "hpp", "hh", "java", "kt", "kts",
+            "scala", "js", "mjs", "cjs", "ts", "mts", "cts", "d.ts", "jsx", "tsx", "rb", "php",
+            "swift", "m", "mm", "cs", "fs", "hs", "ml", "ex", "exs", "erl", "clj", "lua", "pl",
+            "r", "jl", "dart", "zig", "nim", "sh", "bash", "zsh", "fish", "ps1", "bat", "sql",
+            "html", "htm", "css", "scss", "sass", "less", "vue", "svelte",
+        ],
+    ),
+    (
+        "data",
+        &[
+            "json", "jsonl", "yaml", "yml", "toml", "xml", "ini", "cfg", "conf", "env", "lock",
+            "sqlite", "sqlite3", "db", "parquet", "avro", "proto",
+        ],
+    ),
+    (
+        "archives",
+        &[
+            "zip",
+            "tar",
+            "gz",
+            "tgz",
+            "bz2",
+            "tbz2",
+            "xz",
+            "txz",
+            "zst",
+            "7z",
+            "rar",
+            "lz",
+            "lz4",
+            "lzma",
+            "br",
+            "z",
+            "tar.gz",
+            "tar.bz2",
+            "tar.xz",
+            "tar.zst",
+            "tar.lz",
+            "tar.lz4",
+            "tar.lzma",
+            "tar.lzo",
+            "tar.br",
+            "tar.z",
+            "pkg.tar.zst",
+            "pkg.tar.xz",
+            "jar",
+            "war",
+            "whl",
+            "deb",
+            "rpm",
+            "apk",
+            "dmg",
+            "iso",
+        ],
+    ),
+    (
+        "audio",
+        &[
+            "mp3", "wav", "flac", "ogg", "oga", "opus", "m4a", "aac", "wma", "aiff", "aif", "mid",
+            "midi",
+        ],
+    ),
+    (
+        "video",
+        &[
+            "mp4", "m4v", "mkv", "webm", "mov", "avi", "wmv", "flv", "mpg", "mpeg", "3gp", "ogv",
+        ],
+    ),
+    ("fonts", &["ttf", "otf", "woff", "woff2", "eot"]),
+    (
+        "executables",
+        &[
+            "exe", "dll", "so", "dylib", "o", "a", "lib", "bin", "elf", "msi", "app", "wasm",
+        ],
+    ),
+];
+
+/// Maps extension keys to categories.
+#[derive(Debug, Clone)]
+pub struct Taxonomy {
+    categories: HashMap<String, String>,
+}
+
+impl Default for Taxonomy {
+    fn default() -> Self {
+        Self::builtin()
+    }
+}
+
+impl Taxonomy {
+    /// The built-in taxonomy.
+    pub fn builtin() -> Self {
+        let mut taxonomy = Self::empty();
+        for (category, extensions) in BUILTIN {
+            taxonomy.assign(category, *extensions);
+        }
+        taxonomy
+    }
+
+    /// A taxonomy placing everything under [`OTHER`].
+    pub fn empty() -> Self {
+        Self {
+            categories: HashMap::new(),
+        }
+    }
+
+    /// Places `extensions` under `category`, overriding any earlier
+    /// assignment of them.
+    pub fn assign<I, S>(&mut self, category: &str, extensions: I)
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        for extension in extensions {
+            self.categories
+                .insert(normalize(extension), category.to_string());
+        }
+    }
+
+    /// The category of the extension key `extension`, in any case.
+    pub fn category(&self, extension: &str) -> &str {
+        self.categories
+            .get(&extension.to_lowercase())
+            .map_or(OTHER, String::as_str)
+    }
+
+    /// Groups the extension groups of `index` by category. Categories are
+    /// sorted by name, except that [`OTHER`] comes last.
+    pub fn categorize<'a>(&self, index: &'a ExtensionIndex) -> Vec<Category<'a>> {
+        let mut categories: BTreeMap<&str, Vec<(&'a str, &'a [FileEntry])>> = BTreeMap::new();
+        for (extension, files) in index {
+            categories
+                .entry(self.category(extension))
+                .or_default()
+                .push((extension, files));
+        }
+
+        let other = categories.remove(OTHER);
+        categories
+            .into_iter()
+            .chain(other.map(|groups| (OTHER, groups)))
+            .map(|(name, groups)| Category {
+                name: name.to_string(),
+                groups,
+            })
+            .collect()
+    }
+}
+
+/// The extension groups of an index that fall under one category.
+#[derive(Debug, Clone)]
+pub struct Category<'a> {
+    /// Name of the category (e.g., "images").
+    pub name: String,
+    /// `(extension, files)` pairs in sorted order.
+    pub groups: Vec<(&'a str, &'a [FileEntry])>,
+}
+
+impl<'a> Category<'a> {
+    /// Iterates over every file in the category, group by group.
+    pub fn files(&self) -> impl Iterator<Item = &'a FileEntry> + '_ {
+        self.groups.iter().flat_map(|(_, files)| files.iter())
+    }
+
+    /// Total number of files in the category.
+    pub fn file_count(&self) -> usize {
+        self.groups.iter().map(|(_, files)| files.len()).sum()
+    }
+}
@@ -0,0 +1,196 @@
+//! User configuration loaded from TOML files.
+//!
+//! Settings are read from `$XDG_CONFIG_HOME/fext/config.toml` (falling back
+//! to `~/.config/fext/config.toml`), then from the nearest `.fext.toml` in
+//! the current directory or its ancestors, with later files taking
+//! precedence.
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::Scanner;
+use crate::category::Taxonomy;
+
+/// Name of the project-local configuration file.
+pub const PROJECT_FILE: &str = ".fext.toml";
+
+/// Settings read from a configuration file.
+///
+/// ```toml
+/// # Key for files without an extension (":" by default).
+/// placeholder = "(none)"
+///
+/// # Extra multi-part extensions recognized with --compound.
+/// compound_extensions = ["tar.br"]
+///
+/// [aliases]
+/// jpeg = "jpg"
+/// yml = "yaml"
+/// htm = "html"
+///
+/// [categories]
+/// images = ["jpg", "png", "heic"]
+/// notes = ["md", "txt"]
+///
+/// [defaults]
+/// hidden = true
+/// group_by = "category"
+///
+/// [daemon]
+/// dir = "/home/me/Downloads"
+/// settle = "10s"
+///
+/// [daemon.rules]
+/// pdf = "/home/me/Documents"
+/// jpg = "images"
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Key for files without an extension, replacing
+    /// [`NO_EXTENSION_PLACEHOLDER`].
+    ///
+    /// [`NO_EXTENSION_PLACEHOLDER`]: crate::NO_EXTENSION_PLACEHOLDER
+    pub placeholder: Option<String>,
+    /// Multi-part extensions recognized in compound mode, in addition to
+    /// the built-in ones.
+    pub compound_extensions: Vec<String>,
+    /// Extensions grouped under another, canonical one (alias -> canonical).
+    pub aliases: BTreeMap<String, String>,
+    /// Extensions to place under each category, overriding the built-in
+    /// taxonomy for the listed extensions.
+    pub categories: BTreeMap<String, Vec<String>>,
+    /// Defaults for command-line flags.
+    pub defaults: Defaults,
+    /// Settings for `fext daemon`.
+    pub daemon: DaemonConfig,
+}
+
+/// Defaults for command-line flags. A flag given on the command line always
+/// wins.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Defaults {
+    /// Include hidden files (`--hidden`).
+    pub hidden: Option<bool>,
+    /// Descend into hidden directories (`--hidden-dirs`).
+    pub hidden_dirs: Option<bool>,
+    /// Honor ignore files (`false` is `--no-ignore`).
+    pub ignore: Option<bool>,
+    /// Group multi-part extensions as a whole (`--compound`).
+    pub compound: Option<bool>,
+    /// Keep extensions as written instead of lowercasing them
+    /// (`--case-sensitive`).
+    pub case_sensitive: Option<bool>,
+    /// Maximum depth below the root (`--max-depth`).
+    pub max_depth: Option<usize>,
+    /// How to determine each file's type (`--detect`).
+    pub detect: Option<String>,
+    /// Output format (`--format`).
+    pub format: Option<String>,
+    /// What the listing groups files by (`--group-by`).
+    pub group_by: Option<String>,
+}
+
+/// Settings for `fext daemon`.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DaemonConfig {
+    /// Directory to watch when none is given on the command line.
+    pub dir: Option<PathBuf>,
+    /// How long a file must stay unchanged before it is moved (e.g., "10s").
+    pub settle: Option<String>,
+    /// Destination folder per extension. Relative folders are inside the
+    /// watched directory.
+    pub rules: BTreeMap<String, PathBuf>,
+}
+
+impl Config {
+    /// Reads and parses the configuration file at `path`.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let text = fs::read_to_string(path)?;
+        toml::from_str(&text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Loads the user configuration, then the nearest project configuration
+    /// above `dir`, merging them. Missing files are skipped.
+    pub fn discover(dir: &Path) -> io::Result<Self> {
+        let mut config = Self::default();
+        for path in [user_path(), project_path(dir)].into_iter().flatten() {
+            let loaded = Self::load(&path)
+                .map_err(|e| io::Error::new(e.kind(), format!("{}: {e}", path.display())))?;
+            config.merge(loaded);
+        }
+        Ok(config)
+    }
+
+    /// Applies `other` on top of this configuration: its scalar settings
+    /// replace these, and its map entries are added or replace existing ones.
+    pub fn merge(&mut self, other: Self) {
+        if other.placeholder.is_some() {
+            self.placeholder = other.placeholder;
+        }
+        self.compound_extensions.extend(other.compound_extensions);
+        self.aliases.extend(other.aliases);
+        self.categories.extend(other.categories);
+
+        let (defaults, other_defaults) = (&mut self.defaults, other.defaults);
+        defaults.hidden = other_defaults.hidden.or(defaults.hidden);
+        defaults.hidden_dirs = other_defaults.hidden_dirs.or(defaults.hidden_dirs);
+        defaults.ignore = other_defaults.ignore.or(defaults.ignore);
+        defaults.compound = other_defaults.compound.or(defaults.compound);
+        defaults.case_sensitive = other_defaults.case_sensitive.or(defaults.case_sensitive);
+        defaults.max_depth = other_defaults.max_depth.or(defaults.max_depth);
+        defaults.detect = other_defaults.detect.or(defaults.detect.take());
+        defaults.format = other_defaults.format.or(defaults.format.take());
+        defaults.group_by = other_defaults.group_by.or(defaults.group_by.take());
+
+        let (daemon, other_daemon) = (&mut self.daemon, other.daemon);
+        daemon.dir = other_daemon.dir.or(daemon.dir.take());
+        daemon.settle = other_daemon.settle.or(daemon.settle.take());
+        daemon.rules.extend(other_daemon.rules);
+    }
+
+    /// Applies the extension settings of this configuration to `scanner`.
+    pub fn configure(&self, scanner: Scanner) -> Scanner {
+        let mut scanner = scanner
+            .aliases(self.aliases.clone())
+            .compound_extensions(self.compound_extensions.iter().cloned());
+        if let Some(placeholder) = &self.placeholder {
+            scanner = scanner.no_extension_placeholder(placeholder.clone());
+        }
+        scanner
+    }
+
+    /// The built-in taxonomy with this configuration's categories applied.
+    pub fn taxonomy(&self) -> Taxonomy {
+        let mut taxonomy = Taxonomy::builtin();
+        for (category, extensions) in &self.categories {
+            taxonomy.assign(category, extensions);
+        }
+        taxonomy
+    }
+}
+
+/// Location of the user configuration file, if a config directory is known.
+pub fn user_path() -> Option<PathBuf> {
+    let config_dir = env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))
+        .or_else(|| env::var_os("APPDATA").map(PathBuf::from))?;
+
+    Some(config_dir.join("fext").join("config.toml")).filter(|path| path.is_file())
+}
+
+/// The nearest [`PROJECT_FILE`] in `dir` or its ancestors.
+pub fn project_path(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+        .map(|dir| dir.join(PROJECT_FILE))
+        .find(|path| path.is_file())
+}
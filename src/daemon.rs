@@ -0,0 +1,228 @@
+//! Moving files out of a watched directory as they arrive.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::{Duration, Instant, SystemTime};
+
+use notify::{EventKind, RecursiveMode, Watcher};
+
+use crate::Scanner;
+use crate::extension::normalize;
+use crate::journal::{self, Journal, Outcome};
+use crate::organize::{Move, Plan};
+
+/// How long a file must stay unchanged before it is moved, by default.
+pub const DEFAULT_SETTLE: Duration = Duration::from_secs(5);
+
+/// How often pending files are checked.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Where files with each extension key go.
+#[derive(Debug, Clone, Default)]
+pub struct Rules {
+    destinations: HashMap<String, PathBuf>,
+}
+
+impl Rules {
+    /// Creates an empty set of rules, which moves nothing.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sends files with `extension` to the folder `destination`, replacing
+    /// any earlier rule for it. Relative destinations are inside the watched
+    /// directory.
+    pub fn rule(mut self, extension: &str, destination: impl Into<PathBuf>) -> Self {
+        self.destinations
+            .insert(normalize(extension), destination.into());
+        self
+    }
+
+    /// The folder for files with `extension`, if there is a rule for it.
+    pub fn destination(&self, extension: &str) -> Option<&Path> {
+        self.destinations
+            .get(&extension.to_lowercase())
+            .map(PathBuf::as_path)
+    }
+
+    /// Whether there are no rules.
+    pub fn is_empty(&self) -> bool {
+        self.destinations.is_empty()
+    }
+}
+
+/// A file that changed recently, and how it looked when last checked.
+#[derive(Debug, Clone, Copy)]
+struct Pending {
+    size: u64,
+    modified: Option<SystemTime>,
+    since: Instant,
+}
+
+/// Watches a directory and moves each file that arrives in it according to
+/// its [`Rules`], once the file has stopped changing. Every move is recorded
+/// in one journal per session so the session can be reviewed and undone.
+#[derive(Debug)]
+pub struct Daemon {
+    scanner: Scanner,
+    root: PathBuf,
+    rules: Rules,
+    settle: Duration,
+    journal: Option<Journal>,
+    pending: HashMap<PathBuf, Pending>,
+}
+
+impl Daemon {
+    /// Prepares to watch the files directly inside `root`, keying them with
+    /// `scanner` (e.g., for aliases or hidden files).
+    pub fn new(scanner: Scanner, root: impl AsRef<Path>, rules: Rules) -> io::Result<Self> {
+        Ok(Self {
+            scanner: scanner.max_depth(Some(1)),
+            root: fs::canonicalize(root)?,
+            rules,
+            settle: DEFAULT_SETTLE,
+            journal: None,
+            pending: HashMap::new(),
+        })
+    }
+
+    /// Only move files whose size and modification time have not changed for
+    /// `settle`, so that partial downloads stay put. [`DEFAULT_SETTLE`] by
+    /// default.
+    pub fn settle(mut self, settle: Duration) -> Self {
+        self.settle = settle;
+        self
+    }
+
+    /// The journal of this session, once anything was moved.
+    pub fn journal(&self) -> Option<&Journal> {
+        self.journal.as_ref()
+    }
+
+    /// Watches the directory, passing each attempted move and its outcome to
+    /// `on_move`. A move that fails, such as one to a destination that cannot
+    /// be written, is passed on as [`Outcome::Failed`] and the daemon keeps
+    /// going. Runs until watching fails, the journal cannot be written, or
+    /// `on_move` returns an error.
+    pub fn run(
+        &mut self,
+        mut on_move: impl FnMut(&Move, &Outcome) -> io::Result<()>,
+    ) -> io::Result<()> {
+        let (sender, events) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(sender).map_err(io::Error::other)?;
+        watcher
+            .watch(&self.root, RecursiveMode::NonRecursive)
+            .map_err(io::Error::other)?;
+
+        loop {
+            match events.recv_timeout(POLL_INTERVAL) {
+                Ok(event) => {
+                    let event = event.map_err(io::Error::other)?;
+                    if matches!(event.kind, EventKind::Access(_)) {
+                        continue;
+                    }
+                    for path in event.paths {
+                        self.observe(&path);
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => return Ok(()),
+            }
+
+            for relative in self.settled() {
+                if let Some((planned, outcome)) = self.move_file(&relative)? {
+                    on_move(&planned, &outcome)?;
+                }
+            }
+        }
+    }
+
+    /// Starts or restarts the settle clock of the file at `path`.
+    fn observe(&mut self, path: &Path) {
+        let Ok(relative) = path.strip_prefix(&self.root) else {
+            return;
+        };
+        // Only files directly inside the root are moved.
+        if relative.components().count() != 1 {
+            return;
+        }
+        if let Ok(metadata) = fs::metadata(path)
+            && metadata.is_file()
+        {
+            self.pending.insert(
+                relative.to_path_buf(),
+                Pending {
+                    size: metadata.len(),
+                    modified: metadata.modified().ok(),
+                    since: Instant::now(),
+                },
+            );
+        }
+    }
+
+    /// Removes and returns the pending files that have not changed for the
+    /// settle time. Files that disappeared are dropped, and files that
+    /// changed since the last check start over.
+    fn settled(&mut self) -> Vec<PathBuf> {
+        let mut settled = Vec::new();
+        self.pending.retain(|relative, pending| {
+            let Ok(metadata) = fs::metadata(self.root.join(relative)) else {
+                return false;
+            };
+            let modified = metadata.modified().ok();
+            if metadata.len() != pending.size || modified != pending.modified {
+                *pending = Pending {
+                    size: metadata.len(),
+                    modified,
+                    since: Instant::now(),
+                };
+                true
+            } else if pending.since.elapsed() >= self.settle {
+                settled.push(relative.clone());
+                false
+            } else {
+                true
+            }
+        });
+        settled.sort();
+        settled
+    }
+
+    /// Moves the file at `relative` to the folder its rule names, if it has
+    /// one, journaling the move first.
+    fn move_file(&mut self, relative: &Path) -> io::Result<Option<(Move, Outcome)>> {
+        let Some((extension, file)) = self.scanner.scan_file(&self.root, relative)? else {
+            return Ok(None);
+        };
+        let Some(folder) = self.rules.destination(&extension) else {
+            return Ok(None);
+        };
+
+        let candidate = Move {
+            source: file.path().to_path_buf(),
+            destination: folder.join(file.file_name()),
+            size: file.size(),
+            modified: file.modified(),
+        };
+        let plan = Plan::from_moves(&self.root, [candidate]);
+        if let Some(conflict) = plan.conflicts().first() {
+            return Ok(Some((conflict.clone(), Outcome::Conflict)));
+        }
+
+        let journal = match &mut self.journal {
+            Some(journal) => {
+                journal.extend(plan.moves())?;
+                journal
+            }
+            None => self.journal.insert(Journal::create(
+                journal::new_path(&self.root, "daemon"),
+                &self.root,
+                plan.moves(),
+            )?),
+        };
+        Ok(journal.run()?.pop())
+    }
+}
@@ -0,0 +1,369 @@
+//! Content-based file type detection from leading "magic" bytes.
+
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+/// How the scanner decides which group a file belongs to.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Detect {
+    /// Group by the file's declared extension only.
+    #[default]
+    Extension,
+    /// Read each file's leading bytes and group by the detected content type,
+    /// falling back to the declared extension when no signature matches.
+    Content,
+}
+
+/// A file type recognized by its content.
+#[derive(Debug, PartialEq, Eq)]
+pub struct ContentType {
+    /// Short name of the type (e.g., "png").
+    pub name: &'static str,
+    /// Extensions commonly used for this type, canonical one first. Files
+    /// detected as this type are grouped under the canonical extension.
+    pub extensions: &'static [&'static str],
+}
+
+impl ContentType {
+    /// The extension files of this type are grouped under.
+    pub fn canonical_extension(&self) -> &'static str {
+        self.extensions[0]
+    }
+
+    /// Whether `extension` (lowercased) is a usual extension for this type.
+    /// For a compound extension such as "tar.gz" the last part decides.
+    pub fn accepts(&self, extension: &str) -> bool {
+        let last = extension.rsplit('.').next().unwrap_or(extension);
+        self.extensions.contains(&extension) || self.extensions.contains(&last)
+    }
+}
+
+/// A type is detected when any of its patterns matches. A pattern matches
+/// when every `(offset, bytes)` part is present in the header.
+struct Signature {
+    content_type: ContentType,
+    patterns: &'static [&'static [(usize, &'static [u8])]],
+}
+
+/// Number of leading bytes read from each file; enough to cover the tar
+/// header magic at offset 257.
+const HEADER_LEN: usize = 512;
+
+// Checked in order, so a type whose pattern is a prefix of another type's
+// pattern must come after it.
+const SIGNATURES: &[Signature] = &[
+    Signature {
+        content_type: ContentType {
+            name: "png",
+            extensions: &["png"],
+        },
+        patterns: &[&[(0, b"\x89PNG\r\n\x1a\n")]],
+    },
+    Signature {
+        content_type: ContentType {
+            name: "jpeg",
+            extensions: &["jpg", "jpeg", "jpe", "jfif"],
+        },
+        patterns: &[&[(0, b"\xff\xd8\xff")]],
+    },
+    Signature {
+        content_type: ContentType {
+            name: "gif",
+            extensions: &["gif"],
+        },
+        patterns: &[&[(0, b"GIF87a")], &[(0, b"GIF89a")]],
+    },
+    Signature {
+        content_type: ContentType {
+            name: "webp",
+            extensions: &["webp"],
+        },
+        patterns: &[&[(0, b"RIFF"), (8, b"WEBP")]],
+    },
+    Signature {
+        content_type: ContentType {
+            name: "tiff",
+            extensions: &["tiff", "tif", "dng", "nef", "cr2", "arw"],
+        },
+        patterns: &[&[(0, b"II*\0")], &[(0, b"MM\0*")]],
+    },
+    Signature {
+        content_type: ContentType {
+            name: "psd",
+            extensions: &["psd"],
+        },
+        patterns: &[
+            &[(0, b"8BPS"), (4, b"\0\x01")],
+            &[(0, b"8BPS"), (4, b"\0\x02")],
+        ],
+    },
+    Signature {
+        content_type: ContentType {
+            name: "pdf",
+            extensions: &["pdf", "ai"],
+        },
+        patterns: &[&[(0, b"%PDF-")]],
+    },
+    Signature {
+        content_type: ContentType {
+            name: "zip",
+            extensions: &[
+                "zip", "jar", "war", "ear", "apk", "aar", "ipa", "xpi", "whl", "nupkg", "vsix",
+                "epub", "docx", "xlsx", "pptx", "odt", "ods", "odp", "odg", "kmz", "3mf",
+            ],
+        },
+        patterns: &[&[(0, b"PK\x03\x04")], &[(0, b"PK\x05\x06")]],
+    },
+    Signature {
+        content_type: ContentType {
+            name: "gzip",
+            extensions: &["gz", "tgz", "svgz"],
+        },
+        patterns: &[&[(0, b"\x1f\x8b")]],
+    },
+    Signature {
+        content_type: ContentType {
+            name: "bzip2",
+            extensions: &["bz2", "tbz", "tbz2"],
+        },
+        patterns: &[&[(0, b"BZh")]],
+    },
+    Signature {
+        content_type: ContentType {
+            name: "xz",
+            extensions: &["xz", "txz"],
+        },
+        patterns: &[&[(0, b"\xfd7zXZ\0")]],
+    },
+    Signature {
+        content_type: ContentType {
+            name: "zstd",
+            extensions: &["zst", "tzst"],
+        },
+        patterns: &[&[(0, b"\x28\xb5\x2f\xfd")]],
+    },
+    Signature {
+        content_type: ContentType {
+            name: "7z",
+            extensions: &["7z"],
+        },
+        patterns: &[&[(0, b"7z\xbc\xaf\x27\x1c")]],
+    },
+    Signature {
+        content_type: ContentType {
+            name: "rar",
+            extensions: &["rar"],
+        },
+        patterns: &[&[(0, b"Rar!\x1a\x07")]],
+    },
+    Signature {
+        content_type: ContentType {
+            name: "tar",
+            extensions: &["tar"],
+        },
+        patterns: &[&[(257, b"ustar")]],
+    },
+    Signature {
+        content_type: ContentType {
+            name: "elf",
+            extensions: &["elf", "so", "o", "ko", "bin", "out", "axf", "prx"],
+        },
+        patterns: &[&[(0, b"\x7fELF")]],
+    },
+    Signature {
+        content_type: ContentType {
+            name: "pe",
+            extensions: &["exe", "dll", "sys", "efi", "scr", "ocx", "cpl", "com"],
+        },
+        patterns: &[&[(0, b"MZ")]],
+    },
+    Signature {
+        content_type: ContentType {
+            name: "mach-o",
+            extensions: &["macho", "dylib", "bundle", "o"],
+        },
+        patterns: &[&[(0, b"\xcf\xfa\xed\xfe")]],
+    },
+    Signature {
+        content_type: ContentType {
+            name: "wasm",
+            extensions: &["wasm"],
+        },
+        patterns: &[&[(0, b"\0asm")]],
+    },
+    Signature {
+        content_type: ContentType {
+            name: "sqlite",
+            extensions: &["sqlite", "sqlite3", "db", "db3"],
+        },
+        patterns: &[&[(0, b"SQLite format 3\0")]],
+    },
+    Signature {
+        content_type: ContentType {
+            name: "wav",
+            extensions: &["wav"],
+        },
+        patterns: &[&[(0, b"RIFF"), (8, b"WAVE")]],
+    },
+    Signature {
+        content_type: ContentType {
+            name: "avi",
+            extensions: &["avi"],
+        },
+        patterns: &[&[(0, b"RIFF"), (8, b"AVI ")]],
+    },
+    Signature {
+        content_type: ContentType {
+            name: "mp3",
+            extensions: &["mp3"],
+        },
+        patterns: &[&[(0, b"ID3")]],
+    },
+    Signature {
+        content_type: ContentType {
+            name: "flac",
+            extensions: &["flac"],
+        },
+        patterns: &[&[(0, b"fLaC")]],
+    },
+    Signature {
+        content_type: ContentType {
+            name: "ogg",
+            extensions: &["ogg", "oga", "ogv", "opus"],
+        },
+        patterns: &[&[(0, b"OggS")]],
+    },
+    Signature {
+        content_type: ContentType {
+            name: "mp4",
+            extensions: &["mp4", "m4a", "m4v", "mov", "heic", "heif", "avif", "3gp"],
+        },
+        patterns: &[&[(4, b"ftyp")]],
+    },
+    Signature {
+        content_type: ContentType {
+            name: "matroska",
+            extensions: &["mkv", "webm", "mka"],
+        },
+        patterns: &[&[(0, b"\x1aE\xdf\xa3")]],
+    },
+    Signature {
+        content_type: ContentType {
+            name: "woff",
+            extensions: &["woff"],
+        },
+        patterns: &[&[(0, b"wOFF")]],
+    },
+    Signature {
+        content_type: ContentType {
+            name: "woff2",
+            extensions: &["woff2"],
+        },
+        patterns: &[&[(0, b"wOF2")]],
+    },
+];
+
+/// Classifies `header`, the leading bytes of a file, against the built-in
+/// signature table.
+pub fn detect(header: &[u8]) -> Option<&'static ContentType> {
+    SIGNATURES
+        .iter()
+        .find(|signature| {
+            signature.patterns.iter().any(|parts| {
+                parts.iter().all(|&(offset, magic)| {
+                    header
+                        .get(offset..offset + magic.len())
+                        .is_some_and(|bytes| bytes == magic)
+                })
+            }) && is_plausible(signature.content_type.name, header)
+        })
+        .map(|signature| &signature.content_type)
+}
+
+/// Further checks for types whose magic is a few printable characters, so
+/// that a text file that merely starts with them (e.g., "MZ is a band") is
+/// not taken for one.
+fn is_plausible(name: &str, header: &[u8]) -> bool {
+    match name {
+        // The DOS header points at the PE header, which must follow.
+        "pe" => header
+            .get(0x3c..0x40)
+            .map(|offset| u32::from_le_bytes(offset.try_into().unwrap()) as usize)
+            .and_then(|offset| header.get(offset..offset.checked_add(4)?))
+            .is_some_and(|magic| magic == b"PE\0\0"),
+        // A block size digit, then the magic of the first block or, for an
+        // empty stream, of the end of the stream.
+        "bzip2" => {
+            header
+                .get(3)
+                .is_some_and(|size| (b'1'..=b'9').contains(size))
+                && header.get(4..10).is_some_and(|magic| {
+                    magic == b"\x31\x41\x59\x26\x53\x59" || magic == b"\x17\x72\x45\x38\x50\x90"
+                })
+        }
+        // An ID3v2 tag of a known major version.
+        "mp3" => header
+            .get(3..5)
+            .is_some_and(|version| (2..=4).contains(&version[0]) && version[1] != 0xff),
+        _ => true,
+    }
+}
+
+/// The built-in type called `name` (e.g., "png"), if there is one.
+pub fn by_name(name: &str) -> Option<&'static ContentType> {
+    SIGNATURES
+        .iter()
+        .map(|signature| &signature.content_type)
+        .find(|content_type| content_type.name == name)
+}
+
+/// Reads the leading bytes of the file at `path` and classifies them.
+pub fn detect_file(path: impl AsRef<Path>) -> io::Result<Option<&'static ContentType>> {
+    let mut header = Vec::with_capacity(HEADER_LEN);
+    File::open(path)?
+        .take(HEADER_LEN as u64)
+        .read_to_end(&mut header)?;
+    Ok(detect(&header))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn name(header: &[u8]) -> Option<&'static str> {
+        detect(header).map(|content_type| content_type.name)
+    }
+
+    #[test]
+    fn detects_png() {
+        assert_eq!(name(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR"), Some("png"));
+    }
+
+    #[test]
+    fn detects_pe_only_with_its_header() {
+        let mut header = vec![0; 0x84];
+        header[..2].copy_from_slice(b"MZ");
+        header[0x3c..0x40].copy_from_slice(&0x80u32.to_le_bytes());
+        header[0x80..].copy_from_slice(b"PE\0\0");
+        assert_eq!(name(&header), Some("pe"));
+
+        // Truncated before the PE header, or pointing past any header.
+        assert_eq!(name(&header[..0x82]), None);
+        header[0x3c..0x40].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert_eq!(name(&header), None);
+    }
+
+    #[test]
+    fn text_starting_with_a_magic_is_not_detected() {
+        assert_eq!(name(b"MZ is a band from the eighties.\n"), None);
+        assert_eq!(name(b"BZh, said the bee.\n"), None);
+        assert_eq!(name(b"ID3 tags explained\n"), None);
+    }
+
+    #[test]
+    fn detects_mp3_by_its_id3_tag() {
+        assert_eq!(name(b"ID3\x04\0\0\0\0\x01\x00"), Some("mp3"));
+        assert_eq!(name(b"ID3\x09\0\0\0\0\x01\x00"), None);
+    }
+}
@@ -0,0 +1,210 @@
+//! Finding files with identical content.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::{self, File};
+use std::io;
+use std::path::Path;
+
+use crate::cache::Cache;
+use crate::{ExtensionIndex, FileEntry};
+
+/// Files with identical content, in path order. The first is the one
+/// considered the original; the others are redundant copies.
+#[derive(Debug, Clone)]
+pub struct DuplicateSet<'a> {
+    /// Size of each file in bytes.
+    pub size: u64,
+    /// BLAKE3 hash of the content, in hex.
+    pub hash: String,
+    pub files: Vec<&'a FileEntry>,
+}
+
+impl DuplicateSet<'_> {
+    /// The extension key of the original, which the set is listed under.
+    pub fn extension(&self) -> &str {
+        self.files[0].extension()
+    }
+
+    /// The distinct extension keys of the files, sorted.
+    pub fn extensions(&self) -> Vec<&str> {
+        let mut extensions: Vec<&str> = self.files.iter().map(|file| file.extension()).collect();
+        extensions.sort_unstable();
+        extensions.dedup();
+        extensions
+    }
+
+    /// Whether the copies carry different extension keys, e.g. the same
+    /// image saved as both .jpg and .png.
+    pub fn has_mixed_extensions(&self) -> bool {
+        self.extensions().len() > 1
+    }
+
+    /// Bytes freed by deleting every copy but the original.
+    pub fn reclaimable_bytes(&self) -> u64 {
+        self.size * (self.files.len() as u64 - 1)
+    }
+}
+
+/// The redundant copies with one extension key and the bytes they take up.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Reclaimable {
+    pub count: usize,
+    pub bytes: u64,
+}
+
+/// The duplicates found in a scanned tree.
+#[derive(Debug, Clone)]
+pub struct Duplicates<'a> {
+    /// Duplicate sets grouped by the extension key of their original, each
+    /// group ordered by size (largest first), then path.
+    pub groups: BTreeMap<String, Vec<DuplicateSet<'a>>>,
+    /// Redundant copies per extension key.
+    pub reclaimable: BTreeMap<String, Reclaimable>,
+}
+
+impl<'a> Duplicates<'a> {
+    /// Finds the files in `index` with identical content. Files are first
+    /// grouped by size, and only those sharing a size with another file are
+    /// hashed. Empty files are ignored, as are files that disappeared since
+    /// the scan.
+    ///
+    /// Symlinks and extra hard links to a file share its storage, so
+    /// deleting them would free nothing: symlinks are ignored, and of the
+    /// hard links to one file only the first in path order is considered.
+    ///
+    /// With a `cache` of the scanned tree, hashes of files that have not
+    /// changed since they were cached are reused, and new ones are stored
+    /// in it.
+    pub fn find(index: &'a ExtensionIndex, cache: Option<&Cache>) -> io::Result<Self> {
+        let mut files: Vec<&FileEntry> = index.files().filter(|file| file.size() > 0).collect();
+        files.sort_by(|a, b| a.path().as_os_str().cmp(b.path().as_os_str()));
+
+        let mut by_size: HashMap<u64, Vec<&FileEntry>> = HashMap::new();
+        let mut seen = HashSet::new();
+        for file in files {
+            let path = index.root().join(file.path());
+            let metadata = match fs::symlink_metadata(&path) {
+                Ok(metadata) => metadata,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                Err(e) => {
+                    return Err(io::Error::new(e.kind(), format!("{}: {e}", path.display())));
+                }
+            };
+            if metadata.file_type().is_symlink() {
+                continue;
+            }
+            if let Some(id) = file_id(&metadata)
+                && !seen.insert(id)
+            {
+                continue;
+            }
+            by_size.entry(file.size()).or_default().push(file);
+        }
+
+        let mut sets = Vec::new();
+        for (size, files) in by_size {
+            if files.len() < 2 {
+                continue;
+            }
+
+            let mut by_hash: HashMap<String, Vec<&FileEntry>> = HashMap::new();
+            for file in files {
+                match cached_hash(index.root(), file.path(), cache) {
+                    Ok(hash) => by_hash.entry(hash).or_default().push(file),
+                    Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                    Err(e) => {
+                        let path = index.root().join(file.path());
+                        return Err(io::Error::new(e.kind(), format!("{}: {e}", path.display())));
+                    }
+                }
+            }
+
+            for (hash, mut files) in by_hash {
+                if files.len() > 1 {
+                    files.sort_by(|a, b| a.path().as_os_str().cmp(b.path().as_os_str()));
+                    sets.push(DuplicateSet { size, hash, files });
+                }
+            }
+        }
+
+        let mut duplicates = Self {
+            groups: BTreeMap::new(),
+            reclaimable: BTreeMap::new(),
+        };
+        for set in sets {
+            for file in &set.files[1..] {
+                let reclaimable = duplicates
+                    .reclaimable
+                    .entry(file.extension().to_string())
+                    .or_default();
+                reclaimable.count += 1;
+                reclaimable.bytes += set.size;
+            }
+            duplicates
+                .groups
+                .entry(set.extension().to_string())
+                .or_default()
+                .push(set);
+        }
+        for sets in duplicates.groups.values_mut() {
+            sets.sort_by(|a, b| {
+                b.size.cmp(&a.size).then_with(|| {
+                    a.files[0]
+                        .path()
+                        .as_os_str()
+                        .cmp(b.files[0].path().as_os_str())
+                })
+            });
+        }
+
+        Ok(duplicates)
+    }
+
+    /// The redundant copies across all extensions.
+    pub fn total_reclaimable(&self) -> Reclaimable {
+        self.reclaimable
+            .values()
+            .fold(Reclaimable::default(), |total, reclaimable| Reclaimable {
+                count: total.count + reclaimable.count,
+                bytes: total.bytes + reclaimable.bytes,
+            })
+    }
+}
+
+/// Hashes the file at `relative` under `root`, reusing the hash in `cache`
+/// if the file has not changed since it was stored there.
+fn cached_hash(root: &Path, relative: &Path, cache: Option<&Cache>) -> io::Result<String> {
+    let path = root.join(relative);
+    let Some(cache) = cache else {
+        return hash_file(&path);
+    };
+
+    let metadata = fs::metadata(&path)?;
+    let (size, modified) = (metadata.len(), metadata.modified().ok());
+    if let Some(hash) = cache.hash(relative, size, modified) {
+        return Ok(hash);
+    }
+    let hash = hash_file(&path)?;
+    cache.store_hash(relative, size, modified, hash.clone());
+    Ok(hash)
+}
+
+/// Hashes the content of the file at `path`.
+fn hash_file(path: &Path) -> io::Result<String> {
+    let mut hasher = blake3::Hasher::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(hasher.finalize().to_hex().to_string())
+}
+
+/// The device and inode of a file, which its hard links share.
+#[cfg(unix)]
+fn file_id(metadata: &fs::Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((metadata.dev(), metadata.ino()))
+}
+
+/// Hard links cannot be told apart from copies here.
+#[cfg(not(unix))]
+fn file_id(_metadata: &fs::Metadata) -> Option<(u64, u64)> {
+    None
+}
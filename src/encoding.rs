@@ -0,0 +1,110 @@
+//! Rendering of file names that may not be valid UTF-8.
+//!
+//! Human-readable output escapes each invalid byte as `\xNN`. Machine
+//! formats stay lossless: a path that is not valid UTF-8 is written as the
+//! base64 encoding of its raw bytes instead of as a string.
+
+use std::borrow::Cow;
+use std::ffi::{OsStr, OsString};
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Renders `name` for humans, escaping every byte that is not part of valid
+/// UTF-8 as `\xNN` (e.g., "caf\xe9.txt").
+pub fn escape(name: &OsStr) -> Cow<'_, str> {
+    if let Some(name) = name.to_str() {
+        return Cow::Borrowed(name);
+    }
+
+    let mut escaped = String::new();
+    for chunk in name.as_encoded_bytes().utf8_chunks() {
+        escaped.push_str(chunk.valid());
+        for byte in chunk.invalid() {
+            // Lowercase hex, so escaped extension keys survive lowercasing.
+            let _ = write!(escaped, "\\x{byte:02x}");
+        }
+    }
+    Cow::Owned(escaped)
+}
+
+/// [`escape`] for a whole path.
+pub fn escape_path(path: &Path) -> Cow<'_, str> {
+    escape(path.as_os_str())
+}
+
+/// The base64 encoding of `name`'s raw bytes if it is not valid UTF-8.
+pub fn base64(name: &OsStr) -> Option<String> {
+    match name.to_str() {
+        Some(_) => None,
+        None => Some(STANDARD.encode(name.as_encoded_bytes())),
+    }
+}
+
+/// Rebuilds a name from the raw bytes encoded by [`base64`].
+fn from_base64(encoded: &str) -> Result<OsString, String> {
+    let bytes = STANDARD.decode(encoded).map_err(|e| e.to_string())?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStringExt;
+        Ok(OsString::from_vec(bytes))
+    }
+
+    // Elsewhere the raw bytes are an internal encoding that cannot be
+    // safely turned back into a name unless it happens to be UTF-8.
+    #[cfg(not(unix))]
+    {
+        String::from_utf8(bytes)
+            .map(OsString::from)
+            .map_err(|_| "non-UTF-8 names are not supported on this platform".to_string())
+    }
+}
+
+/// Serializes a path as a string, or as `{"base64": "..."}` holding its raw
+/// bytes if it is not valid UTF-8.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct SerPath<'a>(pub &'a Path);
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum Repr<S> {
+    Utf8(S),
+    Raw { base64: String },
+}
+
+impl Serialize for SerPath<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.0.to_str() {
+            Some(path) => Repr::Utf8(path).serialize(serializer),
+            None => Repr::<&str>::Raw {
+                base64: STANDARD.encode(self.0.as_os_str().as_encoded_bytes()),
+            }
+            .serialize(serializer),
+        }
+    }
+}
+
+/// Serde adapter (`#[serde(with = "crate::encoding::path")]`) storing a
+/// [`PathBuf`] in the [`SerPath`] representation.
+pub(crate) mod path {
+    use super::*;
+
+    pub(crate) fn serialize<S: Serializer>(path: &Path, serializer: S) -> Result<S::Ok, S::Error> {
+        SerPath(path).serialize(serializer)
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<PathBuf, D::Error> {
+        match Repr::<String>::deserialize(deserializer)? {
+            Repr::Utf8(path) => Ok(PathBuf::from(path)),
+            Repr::Raw { base64 } => from_base64(&base64)
+                .map(PathBuf::from)
+                .map_err(serde::de::Error::custom),
+        }
+    }
+}
@@ -0,0 +1,160 @@
+//! Deriving the extension key a file is grouped under.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::NO_EXTENSION_PLACEHOLDER;
+use crate::encoding;
+
+/// Multi-part extensions recognized in compound mode, where e.g.
+/// "backup.tar.gz" is grouped under "tar.gz" rather than "gz".
+pub const COMPOUND_EXTENSIONS: &[&str] = &[
+    "tar.gz",
+    "tar.bz2",
+    "tar.xz",
+    "tar.zst",
+    "tar.lz",
+    "tar.lz4",
+    "tar.lzma",
+    "tar.lzo",
+    "tar.br",
+    "tar.z",
+    "d.ts",
+    "d.mts",
+    "d.cts",
+    "min.js",
+    "min.css",
+    "js.map",
+    "css.map",
+    "pkg.tar.zst",
+    "pkg.tar.xz",
+];
+
+/// The rules for turning a file name into its extension key.
+#[derive(Debug, Clone)]
+pub(crate) struct KeyRules {
+    /// Compound extensions to recognize, longest first so that e.g.
+    /// "pkg.tar.zst" wins over "tar.zst". Empty outside compound mode.
+    compound: Vec<String>,
+    /// Keys to replace by another (e.g., "jpeg" -> "jpg").
+    aliases: HashMap<String, String>,
+    /// Key for files without an extension.
+    placeholder: String,
+    /// Keep extensions in their original case instead of lowercasing them.
+    case_sensitive: bool,
+}
+
+impl Default for KeyRules {
+    fn default() -> Self {
+        Self {
+            compound: Vec::new(),
+            aliases: HashMap::new(),
+            placeholder: NO_EXTENSION_PLACEHOLDER.to_string(),
+            case_sensitive: false,
+        }
+    }
+}
+
+impl KeyRules {
+    /// Recognize the given compound extensions.
+    pub(crate) fn compound<I, S>(mut self, compound: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut compound: Vec<String> = compound.into_iter().map(normalize).collect();
+        compound.sort_unstable_by(|a, b| b.len().cmp(&a.len()).then_with(|| a.cmp(b)));
+        compound.dedup();
+        self.compound = compound;
+        self
+    }
+
+    /// Replace the keys on the left of each pair by the key on the right.
+    pub(crate) fn aliases<'a>(
+        mut self,
+        aliases: impl IntoIterator<Item = (&'a str, &'a str)>,
+    ) -> Self {
+        self.aliases = aliases
+            .into_iter()
+            .map(|(alias, canonical)| (normalize(alias), normalize(canonical)))
+            .collect();
+        self
+    }
+
+    /// Use `placeholder` as the key of files without an extension.
+    pub(crate) fn placeholder(mut self, placeholder: &str) -> Self {
+        self.placeholder = placeholder.to_string();
+        self
+    }
+
+    /// Keep extensions in their original case.
+    pub(crate) fn case_sensitive(mut self, case_sensitive: bool) -> Self {
+        self.case_sensitive = case_sensitive;
+        self
+    }
+
+    /// The key of files without an extension.
+    pub(crate) fn no_extension_key(&self) -> &str {
+        &self.placeholder
+    }
+
+    /// Determines the grouping key for `path`: a recognized compound
+    /// extension, else its extension in lowercase (or as written when case
+    /// sensitive), or the placeholder if it has none. Aliases are then
+    /// resolved in any case, so that "A.JPEG" and "a.jpeg" both become "jpg"
+    /// even when case sensitive. Bytes that are not valid UTF-8 are escaped (e.g., "\\xe9").
+    pub(crate) fn key(&self, path: &Path) -> String {
+        let Some(extension) = path.extension() else {
+            return self.placeholder.clone();
+        };
+
+        let key = self.compound_key(path).unwrap_or_else(|| {
+            let extension = encoding::escape(extension);
+            if self.case_sensitive {
+                extension.into_owned()
+            } else {
+                extension.to_lowercase()
+            }
+        });
+
+        match self.aliases.get(&key.to_lowercase()) {
+            Some(canonical) => canonical.clone(),
+            None => key,
+        }
+    }
+
+    /// The compound extension `path` ends with, if any.
+    fn compound_key(&self, path: &Path) -> Option<String> {
+        if self.compound.is_empty() {
+            return None;
+        }
+
+        let name = encoding::escape(path.file_name()?);
+        self.compound.iter().find_map(|extension| {
+            let start = name.len().checked_sub(extension.len())?;
+            let suffix = name.get(start..)?;
+            if !suffix.eq_ignore_ascii_case(extension) {
+                return None;
+            }
+
+            // The name needs a stem before the extension, so a file
+            // called just ".tar.gz" does not count.
+            let stem = name[..start].strip_suffix('.')?;
+            if stem.is_empty() || stem.ends_with('.') {
+                return None;
+            }
+
+            Some(if self.case_sensitive {
+                suffix.to_string()
+            } else {
+                extension.clone()
+            })
+        })
+    }
+}
+
+/// Brings a user-supplied extension (e.g., ".JPG") into key form, without
+/// the leading dot and in lowercase.
+pub(crate) fn normalize(extension: impl AsRef<str>) -> String {
+    extension.as_ref().trim_start_matches('.').to_lowercase()
+}
@@ -0,0 +1,90 @@
+//! Path and extension filters applied while scanning.
+
+use std::collections::HashSet;
+use std::path::Path;
+
+use globset::GlobSet;
+
+use crate::extension::normalize;
+
+/// Decides which files a scan records, before they are grouped.
+///
+/// Globs are matched against paths relative to the scanned root, where `*`
+/// also matches `/` (so `*.log` matches "logs/app.log"). Extension filters
+/// compare against the extension key regardless of case, with
+/// [`NO_EXTENSION_PLACEHOLDER`] standing for files without one.
+///
+/// [`NO_EXTENSION_PLACEHOLDER`]: crate::NO_EXTENSION_PLACEHOLDER
+#[derive(Debug, Clone, Default)]
+pub struct Filter {
+    include: Option<GlobSet>,
+    exclude: Option<GlobSet>,
+    extensions: Option<HashSet<String>>,
+    excluded_extensions: HashSet<String>,
+}
+
+impl Filter {
+    /// Creates a filter that accepts everything.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only accept files matching one of `globs`.
+    pub fn include(mut self, globs: GlobSet) -> Self {
+        self.include = Some(globs);
+        self
+    }
+
+    /// Reject files matching any of `globs`. Matching directories are not
+    /// descended into at all.
+    pub fn exclude(mut self, globs: GlobSet) -> Self {
+        self.exclude = Some(globs);
+        self
+    }
+
+    /// Only accept files whose extension is one of `extensions` (e.g.,
+    /// "rs" or ".toml"; case and a leading '.' are ignored).
+    pub fn extensions<I, S>(mut self, extensions: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.extensions = Some(extensions.into_iter().map(normalize).collect());
+        self
+    }
+
+    /// Reject files whose extension is one of `extensions`.
+    pub fn exclude_extensions<I, S>(mut self, extensions: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.excluded_extensions = extensions.into_iter().map(normalize).collect();
+        self
+    }
+
+    /// Whether the directory at `path` (relative to the root) is excluded.
+    pub(crate) fn excludes_dir(&self, path: &Path) -> bool {
+        self.exclude
+            .as_ref()
+            .is_some_and(|globs| globs.is_match(path))
+    }
+
+    /// Whether the file at `path` (relative to the root) with the extension
+    /// key `extension` should be recorded.
+    pub(crate) fn accepts_file(&self, path: &Path, extension: &str) -> bool {
+        let extension = extension.to_lowercase();
+        self.include
+            .as_ref()
+            .is_none_or(|globs| globs.is_match(path))
+            && !self
+                .exclude
+                .as_ref()
+                .is_some_and(|globs| globs.is_match(path))
+            && self
+                .extensions
+                .as_ref()
+                .is_none_or(|extensions| extensions.contains(&extension))
+            && !self.excluded_extensions.contains(&extension)
+    }
+}
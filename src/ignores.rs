@@ -0,0 +1,123 @@
+//! Gitignore-style filtering of the directory walk.
+
+use std::path::Path;
+use std::sync::Arc;
+
+use ignore::Match;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+
+/// Read from every directory from the top level of a git repository down,
+/// ahead of the [`IGNORE_FILES`]. Outside of a repository, such as in a home
+/// directory holding dotfiles, it is left alone as git leaves it.
+const GIT_IGNORE: &str = ".gitignore";
+
+/// Ignore files read from every directory, lowest precedence first. The
+/// fext-specific file wins over the generic one.
+const IGNORE_FILES: &[&str] = &[".ignore", ".fextignore"];
+
+/// Per-repository excludes, read from any directory containing `.git`.
+const GIT_EXCLUDE: &str = ".git/info/exclude";
+
+/// The ignore rules in effect for the directory currently being walked.
+///
+/// Each level holds the matchers of one directory; rules in deeper
+/// directories take precedence over those of their ancestors, as in git.
+/// Levels are shared, so that each directory of a parallel walk can cheaply
+/// hold a stack of its own.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct IgnoreStack {
+    enabled: bool,
+    /// Whether a directory pushed so far is the top level of a git
+    /// repository, so that `.gitignore` files apply.
+    in_repo: bool,
+    levels: Vec<Arc<Vec<Gitignore>>>,
+}
+
+impl IgnoreStack {
+    /// A stack that never ignores anything.
+    pub(crate) fn disabled() -> Self {
+        Self::default()
+    }
+
+    /// A stack holding the rules of every ancestor of `root`, which must be
+    /// an absolute path. The rules of `root` itself are added by
+    /// [`Self::push`] once the walk enters it.
+    pub(crate) fn new(root: &Path) -> Self {
+        let mut stack = Self {
+            enabled: true,
+            in_repo: false,
+            levels: Vec::new(),
+        };
+
+        let mut ancestors: Vec<&Path> = root.ancestors().skip(1).collect();
+        ancestors.reverse();
+        for dir in ancestors {
+            stack.push(dir);
+        }
+
+        stack
+    }
+
+    /// Adds the rules found in `dir` (an absolute path) on top of the stack.
+    pub(crate) fn push(&mut self, dir: &Path) {
+        if !self.enabled {
+            return;
+        }
+
+        // A `.git` file rather than a directory marks a worktree or
+        // submodule, which is a repository all the same.
+        let git = dir.join(".git");
+        self.in_repo |= git.exists();
+
+        let mut files = Vec::new();
+        if git.is_dir() {
+            files.push(dir.join(GIT_EXCLUDE));
+        }
+        if self.in_repo {
+            files.push(dir.join(GIT_IGNORE));
+        }
+        files.extend(IGNORE_FILES.iter().map(|name| dir.join(name)));
+
+        // Invalid lines are skipped rather than failing the scan, as git
+        // does; only a file that cannot be used at all is left out.
+        let matchers = files
+            .into_iter()
+            .filter(|file| file.is_file())
+            .filter_map(|file| {
+                let mut builder = GitignoreBuilder::new(dir);
+                let _ = builder.add(file);
+                builder.build().ok()
+            })
+            .filter(|matcher| !matcher.is_empty())
+            .collect();
+
+        self.levels.push(Arc::new(matchers));
+    }
+
+    /// A copy of this stack with the rules found in `dir` added on top.
+    pub(crate) fn child(&self, dir: &Path) -> Self {
+        let mut child = self.clone();
+        child.push(dir);
+        child
+    }
+
+    /// Whether `path` (an absolute path) is excluded by the rules in effect.
+    pub(crate) fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        // The most specific rule decides, so check from the deepest level
+        // and the highest-precedence file down.
+        for matcher in self
+            .levels
+            .iter()
+            .rev()
+            .flat_map(|level| level.iter().rev())
+        {
+            match matcher.matched(path, is_dir) {
+                Match::None => continue,
+                Match::Ignore(_) => return true,
+                Match::Whitelist(_) => return false,
+            }
+        }
+
+        false
+    }
+}
@@ -0,0 +1,242 @@
+use std::collections::BTreeMap;
+use std::collections::btree_map;
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::detect::{ContentType, Detect};
+
+/// A single file recorded in an [`ExtensionIndex`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileEntry {
+    pub(crate) path: PathBuf,
+    pub(crate) extension: String,
+    pub(crate) size: u64,
+    pub(crate) modified: Option<SystemTime>,
+    pub(crate) detected: Option<&'static ContentType>,
+}
+
+impl FileEntry {
+    /// Path of the file relative to the scanned root
+    /// (e.g., "docs/report.pdf").
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The final component of [`Self::path`] (e.g., "report.pdf").
+    pub fn file_name(&self) -> &OsStr {
+        self.path.file_name().unwrap_or(self.path.as_os_str())
+    }
+
+    /// The extension key derived from the file's name, which may differ
+    /// from the group it was placed in when content detection is enabled.
+    pub fn extension(&self) -> &str {
+        &self.extension
+    }
+
+    /// Size of the file in bytes.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Last modification time, if the platform reports one.
+    pub fn modified(&self) -> Option<SystemTime> {
+        self.modified
+    }
+
+    /// The type detected from the file's content, if detection was enabled
+    /// and a signature matched.
+    pub fn detected(&self) -> Option<&'static ContentType> {
+        self.detected
+    }
+
+    /// The detected type, if it contradicts the declared extension (e.g., a
+    /// ".jpg" that is actually a PNG). Files without an extension make no
+    /// claim about their content and never mismatch. The case of the
+    /// extension does not matter, even when keys are case-sensitive.
+    pub fn mismatch(&self) -> Option<&'static ContentType> {
+        self.detected.filter(|content_type| {
+            self.path.extension().is_some() && !content_type.accepts(&self.extension.to_lowercase())
+        })
+    }
+}
+
+/// Files grouped by their extension, as produced by [`Scanner::scan`].
+///
+/// Groups iterate in alphabetical order of their extension key and the files
+/// within each group are sorted by path.
+///
+/// [`Scanner::scan`]: crate::Scanner::scan
+#[derive(Debug, Clone)]
+pub struct ExtensionIndex {
+    root: PathBuf,
+    detect: Detect,
+    no_extension_key: String,
+    // A BTreeMap keeps the keys (file extensions) sorted alphabetically, so
+    // iteration needs no extra sorting step.
+    groups: BTreeMap<String, Vec<FileEntry>>,
+    pub(crate) unreadable_dirs: Vec<PathBuf>,
+}
+
+impl ExtensionIndex {
+    pub(crate) fn new(root: PathBuf, detect: Detect, no_extension_key: String) -> Self {
+        Self {
+            root,
+            detect,
+            no_extension_key,
+            groups: BTreeMap::new(),
+            unreadable_dirs: Vec::new(),
+        }
+    }
+
+    /// Adds `entry` to the group for `extension`. Call [`Self::sort`] once
+    /// all files have been inserted.
+    pub(crate) fn insert(&mut self, extension: String, entry: FileEntry) {
+        // .entry(key).or_default() gets the Vec for the extension or creates
+        // a new one if it doesn't exist.
+        self.groups.entry(extension).or_default().push(entry);
+    }
+
+    /// Adds `entry` to the group for `extension`, keeping the group sorted.
+    pub(crate) fn insert_sorted(&mut self, extension: String, entry: FileEntry) {
+        let files = self.groups.entry(extension).or_default();
+        let position = files
+            .binary_search_by(|file| file.path.as_os_str().cmp(entry.path.as_os_str()))
+            .unwrap_or_else(|position| position);
+        files.insert(position, entry);
+    }
+
+    /// Removes the file at `path` (relative to the root), returning the group
+    /// it was in and its entry. Groups left empty are dropped.
+    pub(crate) fn remove(&mut self, path: &Path) -> Option<(String, FileEntry)> {
+        let (extension, position) = self.groups.iter().find_map(|(extension, files)| {
+            let position = files.iter().position(|file| file.path == path)?;
+            Some((extension.clone(), position))
+        })?;
+
+        let files = self.groups.get_mut(&extension)?;
+        let entry = files.remove(position);
+        if files.is_empty() {
+            self.groups.remove(&extension);
+        }
+        Some((extension, entry))
+    }
+
+    /// Sorts the files within each group by path.
+    pub(crate) fn sort(&mut self) {
+        for files in self.groups.values_mut() {
+            // Compare the raw path bytes so the order matches a plain string
+            // sort of the rendered paths. Unstable sort is fine since paths
+            // are unique.
+            files.sort_unstable_by(|a, b| a.path.as_os_str().cmp(b.path.as_os_str()));
+        }
+    }
+
+    /// The directory this index was built from.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// How files were assigned to groups.
+    pub fn detect(&self) -> Detect {
+        self.detect
+    }
+
+    /// The key files without an extension are grouped under, by default
+    /// [`NO_EXTENSION_PLACEHOLDER`].
+    ///
+    /// [`NO_EXTENSION_PLACEHOLDER`]: crate::NO_EXTENSION_PLACEHOLDER
+    pub fn no_extension_key(&self) -> &str {
+        &self.no_extension_key
+    }
+
+    /// Files recorded under `extension`, if any.
+    pub fn get(&self, extension: &str) -> Option<&[FileEntry]> {
+        self.groups.get(extension).map(Vec::as_slice)
+    }
+
+    /// Iterates over the extension keys in sorted order.
+    pub fn extensions(&self) -> impl Iterator<Item = &str> {
+        self.groups.keys().map(String::as_str)
+    }
+
+    /// Iterates over `(extension, files)` pairs in sorted order.
+    pub fn iter(&self) -> Groups<'_> {
+        Groups {
+            inner: self.groups.iter(),
+        }
+    }
+
+    /// Iterates over every file in the index, group by group.
+    pub fn files(&self) -> impl Iterator<Item = &FileEntry> {
+        self.groups.values().flatten()
+    }
+
+    /// Number of distinct extension groups.
+    pub fn len(&self) -> usize {
+        self.groups.len()
+    }
+
+    /// Whether the index holds no files at all.
+    pub fn is_empty(&self) -> bool {
+        self.groups.is_empty()
+    }
+
+    /// Number of files whose path relative to the root is not valid UTF-8.
+    pub fn non_utf8_count(&self) -> usize {
+        self.files()
+            .filter(|file| file.path.to_str().is_none())
+            .count()
+    }
+
+    /// Total number of files across all groups.
+    pub fn file_count(&self) -> usize {
+        self.groups.values().map(Vec::len).sum()
+    }
+
+    /// Directories below the root that could not be read, and whose files
+    /// are therefore missing, relative to the root and in path order.
+    pub fn unreadable_dirs(&self) -> &[PathBuf] {
+        &self.unreadable_dirs
+    }
+}
+
+impl<'a> IntoIterator for &'a ExtensionIndex {
+    type Item = (&'a str, &'a [FileEntry]);
+    type IntoIter = Groups<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Iterator over the groups of an [`ExtensionIndex`], created by
+/// [`ExtensionIndex::iter`].
+#[derive(Debug, Clone)]
+pub struct Groups<'a> {
+    inner: btree_map::Iter<'a, String, Vec<FileEntry>>,
+}
+
+impl<'a> Iterator for Groups<'a> {
+    type Item = (&'a str, &'a [FileEntry]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner
+            .next()
+            .map(|(extension, files)| (extension.as_str(), files.as_slice()))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl DoubleEndedIterator for Groups<'_> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner
+            .next_back()
+            .map(|(extension, files)| (extension.as_str(), files.as_slice()))
+    }
+}
+
+impl ExactSizeIterator for Groups<'_> {}
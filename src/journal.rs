@@ -0,0 +1,738 @@
+//! Crash-safe, reversible file moves.
+//!
+//! Every planned move is written to an append-only journal and synced to
+//! disk before any file is touched. Progress is recorded after each move, so
+//! an interrupted run can be resumed and a finished one undone.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::organize::Move;
+
+/// Directory, relative to an organized root, holding its journals.
+pub const JOURNAL_DIR: &str = ".fext/journals";
+
+/// One line of a journal file.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+enum Record {
+    /// First record: the absolute root all other paths are relative to.
+    Begin {
+        #[serde(with = "crate::encoding::path")]
+        root: PathBuf,
+    },
+    /// A move that will be attempted, with the source's size and
+    /// modification time so undo can tell whether the file changed since.
+    Plan {
+        #[serde(with = "crate::encoding::path")]
+        source: PathBuf,
+        #[serde(with = "crate::encoding::path")]
+        destination: PathBuf,
+        size: u64,
+        modified: Option<SystemTime>,
+    },
+    /// A directory the run had to create.
+    CreateDir {
+        #[serde(with = "crate::encoding::path")]
+        path: PathBuf,
+    },
+    /// The planned move with this index crosses file systems, and its copy
+    /// is complete and synced under the partial name. From here on, the
+    /// move is finished rather than started over.
+    Copied { index: usize },
+    /// The planned move with this index was performed.
+    Done { index: usize },
+    /// The planned move with this index was abandoned.
+    Skip { index: usize },
+    /// Every move planned so far has been performed or abandoned.
+    Complete,
+    /// The move with this index was reversed.
+    Restored { index: usize },
+}
+
+/// A planned move and how far it got.
+#[derive(Debug, Clone)]
+struct Entry {
+    planned: Move,
+    copied: bool,
+    done: bool,
+    skipped: bool,
+    restored: bool,
+}
+
+/// What happened to a single move while running or undoing a journal.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    /// The file was moved (or moved back).
+    Moved,
+    /// The move had already happened before an interruption.
+    AlreadyMoved,
+    /// The destination was taken, so the file was left where it was.
+    Conflict,
+    /// The file to move no longer exists.
+    Missing,
+    /// The file changed since the original run and was left in place.
+    Modified,
+    /// Moving the file failed with this error, so it was left where it was.
+    Failed(String),
+}
+
+/// An open journal and the state replayed from it.
+#[derive(Debug)]
+pub struct Journal {
+    path: PathBuf,
+    root: PathBuf,
+    entries: Vec<Entry>,
+    created_dirs: Vec<PathBuf>,
+    complete: bool,
+}
+
+impl Journal {
+    /// Writes a new journal at `path` recording `moves` under `root`, and
+    /// syncs it to disk before returning.
+    pub fn create(path: impl AsRef<Path>, root: &Path, moves: &[Move]) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let root = fs::canonicalize(root)?;
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let mut journal = Self {
+            path,
+            root: root.clone(),
+            entries: Vec::new(),
+            created_dirs: Vec::new(),
+            complete: false,
+        };
+
+        // Write all records in one go, using create_new so an existing
+        // journal is never clobbered.
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&journal.path)?;
+        write_record(&mut file, &Record::Begin { root })?;
+        journal.write_plans(&mut file, moves)?;
+        file.sync_all()?;
+
+        Ok(journal)
+    }
+
+    /// Records more `moves` in the journal, for a long-running process that
+    /// plans moves as it goes. The journal is incomplete until the next
+    /// [`Self::run`].
+    pub fn extend(&mut self, moves: &[Move]) -> io::Result<()> {
+        let mut file = self.append()?;
+        self.write_plans(&mut file, moves)?;
+        file.sync_data()
+    }
+
+    /// Opens an existing journal and replays its records. A truncated final
+    /// line, left by a crash mid-write, is ignored.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let mut lines = BufReader::new(File::open(&path)?).lines().peekable();
+
+        let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+
+        let root = match lines.next().transpose()?.map(|line| parse_record(&line)) {
+            Some(Ok(Record::Begin { root })) => root,
+            _ => return Err(invalid(format!("{} is not a journal", path.display()))),
+        };
+
+        let mut journal = Self {
+            path,
+            root,
+            entries: Vec::new(),
+            created_dirs: Vec::new(),
+            complete: false,
+        };
+
+        while let Some(line) = lines.next() {
+            let line = line?;
+            let record = match parse_record(&line) {
+                Ok(record) => record,
+                Err(_) if lines.peek().is_none() => break,
+                Err(e) => return Err(invalid(format!("{}: {e}", journal.path.display()))),
+            };
+
+            match record {
+                Record::Begin { .. } => return Err(invalid("duplicate begin record".into())),
+                Record::Plan {
+                    source,
+                    destination,
+                    size,
+                    modified,
+                } => {
+                    journal.entries.push(Entry {
+                        planned: Move {
+                            source,
+                            destination,
+                            size,
+                            modified,
+                        },
+                        copied: false,
+                        done: false,
+                        skipped: false,
+                        restored: false,
+                    });
+                    // Moves planned after a completed run make it pending
+                    // again.
+                    journal.complete = false;
+                }
+                Record::CreateDir { path } => journal.created_dirs.push(path),
+                Record::Copied { index } => journal.entry_mut(index)?.copied = true,
+                Record::Done { index } => journal.entry_mut(index)?.done = true,
+                Record::Skip { index } => journal.entry_mut(index)?.skipped = true,
+                Record::Restored { index } => journal.entry_mut(index)?.restored = true,
+                Record::Complete => journal.complete = true,
+            }
+        }
+
+        Ok(journal)
+    }
+
+    /// Where this journal is stored.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The absolute root the journal's moves are relative to.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Whether every planned move has been performed or abandoned.
+    pub fn is_complete(&self) -> bool {
+        self.complete
+    }
+
+    /// Performs every move not yet recorded as done, then marks the journal
+    /// complete. Safe to call again after an interruption: moves that
+    /// happened but were not recorded are detected and recorded.
+    pub fn run(&mut self) -> io::Result<Vec<(Move, Outcome)>> {
+        let mut file = self.append()?;
+        let mut outcomes = Vec::new();
+
+        for index in 0..self.entries.len() {
+            let entry = &self.entries[index];
+            if entry.done || entry.skipped {
+                continue;
+            }
+
+            let planned = entry.planned.clone();
+            let source = self.root.join(&planned.source);
+            let destination = self.root.join(&planned.destination);
+
+            let outcome = match (source.exists(), is_taken(&source, &destination)) {
+                // Moved before the run was interrupted.
+                (false, true) => Outcome::AlreadyMoved,
+                // Copied across file systems before the run was interrupted,
+                // but the source was not removed yet.
+                (true, _) if entry.copied => finish_copy(&source, &destination, &planned),
+                // A move that fails (e.g., because a file is in the way of
+                // the destination folder) is abandoned rather than ending
+                // the run, which would leave the journal to fail again on
+                // every resume.
+                (true, false) => match self.perform(&mut file, index, &source, &destination) {
+                    Ok(()) => Outcome::Moved,
+                    Err(e) => Outcome::Failed(e.to_string()),
+                },
+                (true, true) => Outcome::Conflict,
+                (false, false) => Outcome::Missing,
+            };
+
+            if matches!(outcome, Outcome::Moved | Outcome::AlreadyMoved) {
+                write_record(&mut file, &Record::Done { index })?;
+                self.entries[index].done = true;
+            } else {
+                write_record(&mut file, &Record::Skip { index })?;
+                self.entries[index].skipped = true;
+            }
+            file.sync_data()?;
+
+            outcomes.push((planned, outcome));
+        }
+
+        write_record(&mut file, &Record::Complete)?;
+        file.sync_data()?;
+        self.complete = true;
+
+        Ok(outcomes)
+    }
+
+    /// Moves every performed move back, newest first, then removes any
+    /// directories the run created if they are now empty. Moves an
+    /// interrupted run never got to are abandoned.
+    ///
+    /// Files deleted since the run are reported as [`Outcome::Missing`], and
+    /// files whose size or modification time changed as
+    /// [`Outcome::Modified`]; the latter are only moved back with `force`.
+    pub fn undo(&mut self, force: bool) -> io::Result<Vec<(Move, Outcome)>> {
+        let mut file = self.append()?;
+        let mut outcomes = Vec::new();
+
+        // Abandon the pending moves of an interrupted run so that a later
+        // organize does not resume it.
+        if !self.complete {
+            write_record(&mut file, &Record::Complete)?;
+            file.sync_data()?;
+            self.complete = true;
+        }
+
+        for index in (0..self.entries.len()).rev() {
+            let entry = &self.entries[index];
+            if !entry.done || entry.restored {
+                continue;
+            }
+
+            let planned = entry.planned.clone();
+            let source = self.root.join(&planned.source);
+            let destination = self.root.join(&planned.destination);
+
+            let outcome = match fs::metadata(&destination) {
+                Err(e) if e.kind() == io::ErrorKind::NotFound => Outcome::Missing,
+                Err(e) => return Err(e),
+                Ok(_) if is_taken(&destination, &source) => Outcome::Conflict,
+                Ok(metadata)
+                    if !force
+                        && (metadata.len() != planned.size
+                            || metadata.modified().ok() != planned.modified) =>
+                {
+                    Outcome::Modified
+                }
+                Ok(_) => {
+                    let restored = source
+                        .parent()
+                        .map_or(Ok(()), fs::create_dir_all)
+                        .and_then(|()| rename(&destination, &source, || Ok(())));
+                    match restored {
+                        Ok(()) => {
+                            write_record(&mut file, &Record::Restored { index })?;
+                            file.sync_data()?;
+                            self.entries[index].restored = true;
+                            Outcome::Moved
+                        }
+                        Err(e) => Outcome::Failed(e.to_string()),
+                    }
+                }
+            };
+
+            outcomes.push((planned, outcome));
+        }
+
+        // Directories are removed deepest first; any still holding files
+        // (e.g., skipped ones) are kept.
+        for dir in self.created_dirs.iter().rev() {
+            let _ = fs::remove_dir(self.root.join(dir));
+        }
+
+        Ok(outcomes)
+    }
+
+    /// Moves `source`, planned as the entry at `index`, to `destination`,
+    /// creating its folder first. A copy across file systems is recorded
+    /// before the source is removed.
+    fn perform(
+        &mut self,
+        file: &mut File,
+        index: usize,
+        source: &Path,
+        destination: &Path,
+    ) -> io::Result<()> {
+        if let Some(parent) = destination.parent() {
+            self.create_dirs(file, parent)?;
+        }
+        rename(source, destination, || {
+            write_record(file, &Record::Copied { index })?;
+            file.sync_data()?;
+            self.entries[index].copied = true;
+            Ok(())
+        })
+    }
+
+    /// Writes a plan record for each of `moves` and adds them as pending.
+    fn write_plans(&mut self, file: &mut File, moves: &[Move]) -> io::Result<()> {
+        for planned in moves {
+            write_record(
+                file,
+                &Record::Plan {
+                    source: planned.source.clone(),
+                    destination: planned.destination.clone(),
+                    size: planned.size,
+                    modified: planned.modified,
+                },
+            )?;
+            self.entries.push(Entry {
+                planned: planned.clone(),
+                copied: false,
+                done: false,
+                skipped: false,
+                restored: false,
+            });
+        }
+        if !moves.is_empty() {
+            self.complete = false;
+        }
+        Ok(())
+    }
+
+    /// Creates `dir` and any missing parents, journaling each one created.
+    fn create_dirs(&mut self, file: &mut File, dir: &Path) -> io::Result<()> {
+        if dir.exists() {
+            return Ok(());
+        }
+        if let Some(parent) = dir.parent() {
+            self.create_dirs(file, parent)?;
+        }
+
+        let relative = dir.strip_prefix(&self.root).unwrap_or(dir).to_path_buf();
+        write_record(
+            file,
+            &Record::CreateDir {
+                path: relative.clone(),
+            },
+        )?;
+        file.sync_data()?;
+        fs::create_dir(dir)?;
+        self.created_dirs.push(relative);
+
+        Ok(())
+    }
+
+    fn entry_mut(&mut self, index: usize) -> io::Result<&mut Entry> {
+        self.entries.get_mut(index).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}: unknown move index {index}", self.path.display()),
+            )
+        })
+    }
+
+    /// Opens the journal for appending, first cutting off any truncated
+    /// final line so new records start on a line of their own.
+    fn append(&self) -> io::Result<File> {
+        let contents = fs::read(&self.path)?;
+        let file = OpenOptions::new().append(true).open(&self.path)?;
+
+        if !contents.ends_with(b"\n") {
+            let complete = contents
+                .iter()
+                .rposition(|&b| b == b'\n')
+                .map_or(0, |i| i + 1);
+            file.set_len(complete as u64)?;
+        }
+
+        Ok(file)
+    }
+}
+
+/// A fresh journal path under `root` whose name sorts after every earlier
+/// journal (e.g., ".fext/journals/1792166400000-organize.jsonl").
+pub fn new_path(root: &Path, command: &str) -> PathBuf {
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_millis());
+    root.join(JOURNAL_DIR)
+        .join(format!("{millis:013}-{command}.jsonl"))
+}
+
+/// All journals under `root`, oldest first.
+pub fn list(root: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut journals = match fs::read_dir(root.join(JOURNAL_DIR)) {
+        Ok(entries) => entries
+            .map(|entry| entry.map(|entry| entry.path()))
+            .filter(|path| {
+                path.as_ref().map_or(true, |path| {
+                    path.extension().is_some_and(|ext| ext == "jsonl")
+                })
+            })
+            .collect::<io::Result<Vec<_>>>()?,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+        Err(e) => return Err(e),
+    };
+    journals.sort();
+    Ok(journals)
+}
+
+/// Whether `destination` exists as anything other than `source` itself. On
+/// case-insensitive file systems, a rename that only changes the case of a
+/// name finds its destination already "existing" as the source.
+pub(crate) fn is_taken(source: &Path, destination: &Path) -> bool {
+    destination.exists()
+        && match (fs::canonicalize(source), fs::canonicalize(destination)) {
+            (Ok(source), Ok(destination)) => source != destination,
+            _ => true,
+        }
+}
+
+/// Moves `source` to `destination`. Across file systems, where a rename is
+/// impossible (e.g., for a destination on another disk), the file is copied
+/// and synced to disk under a partial name, `copied` is called, and only then
+/// is the copy put in place and the source removed.
+fn rename(
+    source: &Path,
+    destination: &Path,
+    copied: impl FnOnce() -> io::Result<()>,
+) -> io::Result<()> {
+    match fs::rename(source, destination) {
+        Err(e) if e.kind() == io::ErrorKind::CrossesDevices => {}
+        result => return result,
+    }
+
+    // Copy under a temporary name, so that an interrupted copy is never
+    // mistaken for the finished file. One left by an earlier interruption
+    // is started over.
+    let partial = partial_path(destination);
+    match fs::remove_file(&partial) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+        _ => {}
+    }
+    let placed = copy_synced(source, &partial)
+        .and_then(|()| copied())
+        .and_then(|()| fs::rename(&partial, destination));
+    if let Err(e) = placed {
+        let _ = fs::remove_file(&partial);
+        return Err(e);
+    }
+    fs::remove_file(source)
+}
+
+/// Finishes a move across file systems whose copy was complete when the run
+/// was interrupted: puts the copy in place if it is still under the partial
+/// name, then removes the source. A destination that does not match the
+/// planned size and modification time is not the copy, so it is a conflict.
+fn finish_copy(source: &Path, destination: &Path, planned: &Move) -> Outcome {
+    let finished = match fs::metadata(destination) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            fs::rename(partial_path(destination), destination)
+        }
+        Err(e) => Err(e),
+        Ok(metadata)
+            if metadata.len() != planned.size || metadata.modified().ok() != planned.modified =>
+        {
+            return Outcome::Conflict;
+        }
+        Ok(_) => Ok(()),
+    };
+    match finished.and_then(|()| fs::remove_file(source)) {
+        Ok(()) => Outcome::Moved,
+        Err(e) => Outcome::Failed(e.to_string()),
+    }
+}
+
+/// The name a copy across file systems is written under until it is
+/// complete.
+fn partial_path(destination: &Path) -> PathBuf {
+    let mut partial = destination.as_os_str().to_owned();
+    partial.push(".fext-partial");
+    PathBuf::from(partial)
+}
+
+/// Copies `source` to the new file `destination` with its permissions and
+/// modification time, which undo relies on to tell whether the file changed,
+/// and syncs it to disk.
+fn copy_synced(source: &Path, destination: &Path) -> io::Result<()> {
+    let mut from = File::open(source)?;
+    let metadata = from.metadata()?;
+    let mut to = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(destination)?;
+    io::copy(&mut from, &mut to)?;
+    to.set_permissions(metadata.permissions())?;
+    to.set_modified(metadata.modified()?)?;
+    to.sync_all()
+}
+
+fn parse_record(line: &str) -> serde_json::Result<Record> {
+    serde_json::from_str(line)
+}
+
+fn write_record(out: &mut impl Write, record: &Record) -> io::Result<()> {
+    let mut line = serde_json::to_vec(record)?;
+    line.push(b'\n');
+    // A single write keeps each record intact if the process dies.
+    out.write_all(&line)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::env;
+    use std::process;
+
+    use crate::organize::Plan;
+
+    /// A fresh, empty directory for one test.
+    fn temp_root(name: &str) -> PathBuf {
+        let root = env::temp_dir().join(format!("fext-journal-{}-{name}", process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        fs::canonicalize(root).unwrap()
+    }
+
+    /// A move of `source` into `folder`, as planned from the file on disk.
+    fn planned(root: &Path, source: &str, folder: &str) -> Move {
+        let metadata = fs::metadata(root.join(source)).unwrap();
+        Move {
+            source: PathBuf::from(source),
+            destination: Path::new(folder).join(source),
+            size: metadata.len(),
+            modified: metadata.modified().ok(),
+        }
+    }
+
+    fn outcomes(results: &[(Move, Outcome)]) -> Vec<Outcome> {
+        results.iter().map(|(_, outcome)| outcome.clone()).collect()
+    }
+
+    #[test]
+    fn resumes_after_a_crash_mid_write() {
+        let root = temp_root("resume");
+        fs::write(root.join("a.pdf"), "a").unwrap();
+        fs::write(root.join("b.txt"), "b").unwrap();
+        let moves = [
+            planned(&root, "a.pdf", "pdf"),
+            planned(&root, "b.txt", "txt"),
+        ];
+        let path = root.join("journal.jsonl");
+        Journal::create(&path, &root, &moves).unwrap();
+
+        // The first move happened, but the crash cut its record short.
+        fs::create_dir(root.join("pdf")).unwrap();
+        fs::rename(root.join("a.pdf"), root.join("pdf/a.pdf")).unwrap();
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(br#"{"op":"do"#).unwrap();
+
+        let mut journal = Journal::open(&path).unwrap();
+        assert!(!journal.is_complete());
+        let results = journal.run().unwrap();
+        assert_eq!(outcomes(&results), [Outcome::AlreadyMoved, Outcome::Moved]);
+        assert!(root.join("txt/b.txt").is_file());
+
+        // The truncated line was cut off, so the journal reads back whole.
+        let journal = Journal::open(&path).unwrap();
+        assert!(journal.is_complete());
+        assert!(journal.entries.iter().all(|entry| entry.done));
+    }
+
+    #[test]
+    fn finishes_copies_across_file_systems_after_a_crash() {
+        let root = temp_root("copied");
+        fs::write(root.join("a.pdf"), "a").unwrap();
+        fs::write(root.join("b.txt"), "b").unwrap();
+        fs::write(root.join("c.txt"), "c").unwrap();
+        let moves = [
+            planned(&root, "a.pdf", "pdf"),
+            planned(&root, "b.txt", "txt"),
+            planned(&root, "c.txt", "other"),
+        ];
+        let path = root.join("journal.jsonl");
+        Journal::create(&path, &root, &moves).unwrap();
+
+        // Both copies were recorded; the first was still under its partial
+        // name, the second already in place, and neither source removed. A
+        // stranger then took the third move's destination.
+        for dir in ["pdf", "txt", "other"] {
+            fs::create_dir(root.join(dir)).unwrap();
+        }
+        copy_synced(&root.join("a.pdf"), &root.join("pdf/a.pdf.fext-partial")).unwrap();
+        copy_synced(&root.join("b.txt"), &root.join("txt/b.txt")).unwrap();
+        fs::write(root.join("other/c.txt"), "not c").unwrap();
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        for index in 0..3 {
+            write_record(&mut file, &Record::Copied { index }).unwrap();
+        }
+
+        let results = Journal::open(&path).unwrap().run().unwrap();
+        assert_eq!(
+            outcomes(&results),
+            [Outcome::Moved, Outcome::Moved, Outcome::Conflict]
+        );
+        assert!(!root.join("a.pdf").exists() && !root.join("b.txt").exists());
+        assert_eq!(fs::read(root.join("pdf/a.pdf")).unwrap(), b"a");
+        assert!(!root.join("pdf/a.pdf.fext-partial").exists());
+        assert_eq!(fs::read(root.join("c.txt")).unwrap(), b"c");
+        assert_eq!(fs::read(root.join("other/c.txt")).unwrap(), b"not c");
+    }
+
+    #[test]
+    fn failed_move_is_skipped_and_the_run_completes() {
+        let root = temp_root("failed");
+        fs::write(root.join("pdf"), "in the way").unwrap();
+        fs::write(root.join("a.pdf"), "a").unwrap();
+        fs::write(root.join("b.txt"), "b").unwrap();
+        let moves = [
+            planned(&root, "a.pdf", "pdf"),
+            planned(&root, "b.txt", "txt"),
+        ];
+        let path = root.join("journal.jsonl");
+
+        let results = Journal::create(&path, &root, &moves)
+            .unwrap()
+            .run()
+            .unwrap();
+        assert!(matches!(results[0].1, Outcome::Failed(_)));
+        assert_eq!(results[1].1, Outcome::Moved);
+        assert!(root.join("a.pdf").is_file());
+
+        // Nothing is left to resume.
+        let mut journal = Journal::open(&path).unwrap();
+        assert!(journal.is_complete());
+        assert!(journal.run().unwrap().is_empty());
+    }
+
+    #[test]
+    fn plan_flags_a_destination_folder_taken_by_a_file() {
+        let root = temp_root("blocked");
+        fs::write(root.join("pdf"), "in the way").unwrap();
+        fs::write(root.join("a.pdf"), "a").unwrap();
+
+        let plan = Plan::from_moves(&root, [planned(&root, "a.pdf", "pdf")]);
+        assert!(plan.moves().is_empty());
+        assert_eq!(plan.conflicts().len(), 1);
+    }
+
+    #[test]
+    fn undo_leaves_modified_files_unless_forced() {
+        let root = temp_root("modified");
+        fs::write(root.join("a.pdf"), "a").unwrap();
+        let path = root.join("journal.jsonl");
+        let mut journal = Journal::create(&path, &root, &[planned(&root, "a.pdf", "pdf")]).unwrap();
+        journal.run().unwrap();
+        fs::write(root.join("pdf/a.pdf"), "changed since").unwrap();
+
+        let mut journal = Journal::open(&path).unwrap();
+        assert_eq!(outcomes(&journal.undo(false).unwrap()), [Outcome::Modified]);
+        assert!(root.join("pdf/a.pdf").is_file());
+
+        let mut journal = Journal::open(&path).unwrap();
+        assert_eq!(outcomes(&journal.undo(true).unwrap()), [Outcome::Moved]);
+        assert!(root.join("a.pdf").is_file());
+        assert!(!root.join("pdf").exists());
+
+        // Undoing again finds nothing left to restore.
+        let mut journal = Journal::open(&path).unwrap();
+        assert!(journal.undo(false).unwrap().is_empty());
+    }
+
+    #[test]
+    fn undo_abandons_moves_an_interrupted_run_never_made() {
+        let root = temp_root("abandon");
+        fs::write(root.join("a.pdf"), "a").unwrap();
+        let path = root.join("journal.jsonl");
+        Journal::create(&path, &root, &[planned(&root, "a.pdf", "pdf")]).unwrap();
+
+        let mut journal = Journal::open(&path).unwrap();
+        assert!(journal.undo(false).unwrap().is_empty());
+        assert!(Journal::open(&path).unwrap().is_complete());
+        assert!(root.join("a.pdf").is_file());
+    }
+}
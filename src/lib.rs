@@ -0,0 +1,45 @@
+//! Group files by their file extension.
+//!
+//! The [`Scanner`] walks a directory tree and returns an [`ExtensionIndex`]
+//! mapping every extension (lowercased by default) to the files that carry
+//! it.
+//!
+//! ```no_run
+//! let index = fext::Scanner::new().max_depth(Some(2)).scan(".")?;
+//! for (extension, files) in &index {
+//!     println!("{extension}: {} file(s)", files.len());
+//! }
+//! # Ok::<(), std::io::Error>(())
+//! ```
+
+pub mod cache;
+pub mod case;
+pub mod category;
+pub mod config;
+pub mod daemon;
+pub mod detect;
+pub mod dupes;
+pub mod encoding;
+mod extension;
+mod filter;
+mod ignores;
+mod index;
+pub mod journal;
+pub mod normalize;
+pub mod organize;
+pub mod report;
+mod scanner;
+pub mod snapshot;
+pub mod stats;
+pub mod watch;
+
+pub use category::Taxonomy;
+pub use config::Config;
+pub use detect::Detect;
+pub use extension::COMPOUND_EXTENSIONS;
+pub use filter::Filter;
+pub use index::{ExtensionIndex, FileEntry, Groups};
+pub use scanner::{Hidden, Scanner};
+
+/// Key used for files without an extension (like 'start' or 'LICENSE').
+pub const NO_EXTENSION_PLACEHOLDER: &str = ":";
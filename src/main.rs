@@ -1,88 +1,1091 @@
-use std::collections::BTreeMap;
 use std::env;
-use std::fs;
-
-// Helper to determine the default file extension representation for files
-// without an extension (like 'start' in the example).
-const NO_EXTENSION_PLACEHOLDER: &str = ":";
-
-/// Lists files in the current directory and groups them by their file extension.
-/// It ignores directories and hidden files (starting with '.').
-fn run_file_sorter() -> Result<(), Box<dyn std::error::Error>> {
-    // 1. Get the current working directory path.
-    let current_dir = env::current_dir()?;
-
-    // Print the directory being scanned for context.
-    println!("Scanning directory: {}\n", current_dir.display());
-
-    // Use a BTreeMap to store results. This map automatically keeps the keys
-    // (file extensions) sorted alphabetically, ensuring the final output
-    // is organized as requested.
-    let mut files_by_extension: BTreeMap<String, Vec<String>> = BTreeMap::new();
-
-    // 2. Iterate over the entries in the current directory.
-    for entry in fs::read_dir(&current_dir)? {
-        let entry = entry?;
-        let path = entry.path();
-
-        // 3. Process only regular files.
-        if path.is_file() {
-            // Extract the filename as a string.
-            if let Some(filename) = path.file_name().and_then(|s| s.to_str()) {
-                // Skip files that start with '.' (hidden files) for cleaner output.
-                if filename.starts_with('.') {
-                    continue;
-                }
-
-                // 4. Determine the file extension.
-                let extension = match path.extension().and_then(|ext| ext.to_str()) {
-                    // If an extension exists, convert it to lowercase for grouping.
-                    Some(ext) => ext.to_lowercase(),
-                    // If no extension, use the defined placeholder (':').
-                    None => NO_EXTENSION_PLACEHOLDER.to_string(),
-                };
-
-                // Get the file name (e.g., "document.pdf" -> "document.pdf")
-                let filename_only = filename.to_string();
-
-                // 5. Insert the filename into the correct extension group.
-                // .entry(key).or_default() gets the Vec<String> for the extension
-                // or creates a new one if it doesn't exist.
-                files_by_extension
-                    .entry(extension)
-                    .or_default()
-                    .push(filename_only);
+use std::io::{self, BufWriter, Write};
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::Duration;
+
+use clap::error::ErrorKind;
+use clap::parser::ValueSource;
+use clap::{Args, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
+use fext::cache::{self, Cache};
+use fext::daemon::{self, Daemon, Rules};
+use fext::dupes::Duplicates;
+use fext::encoding::escape_path;
+use fext::journal::{self, Journal, Outcome};
+use fext::normalize::{self, Canonical};
+use fext::organize::{self, Move, Plan};
+use fext::snapshot::{self, Snapshot};
+use fext::watch::LiveIndex;
+use fext::{Config, Detect, ExtensionIndex, Filter, Hidden, Scanner, Taxonomy, report, stats};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use serde_json::Value;
+
+/// How the grouping is written to stdout.
+#[derive(ValueEnum, Clone, Copy, Debug, Default)]
+enum Format {
+    /// Human-readable "extension:" headers followed by "- file" lines.
+    #[default]
+    Text,
+    /// One pretty-printed JSON document, or an array of one per root when
+    /// several are scanned.
+    Json,
+    /// One comma-separated row per file.
+    Csv,
+    /// One tab-separated row per file.
+    Tsv,
+}
+
+/// How files are assigned to groups.
+#[derive(ValueEnum, Clone, Copy, Debug, Default)]
+enum DetectMode {
+    /// Use the file name's extension.
+    #[default]
+    Extension,
+    /// Read each file's leading bytes and group by the detected type,
+    /// reporting the declared extension alongside.
+    Content,
+}
+
+impl From<DetectMode> for Detect {
+    fn from(mode: DetectMode) -> Self {
+        match mode {
+            DetectMode::Extension => Detect::Extension,
+            DetectMode::Content => Detect::Content,
+        }
+    }
+}
+
+/// What the listing groups files by.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum GroupBy {
+    /// One group per extension.
+    #[default]
+    Extension,
+    /// Extension groups nested under categories such as images or code.
+    Category,
+}
+
+/// Group files by their file extension.
+#[derive(Parser, Debug)]
+#[command(version, about)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Read settings from this TOML file, on top of the user and project
+    /// configuration.
+    #[arg(long, global = true, value_name = "FILE")]
+    config: Option<PathBuf>,
+
+    /// Ignore the user configuration and any .fext.toml.
+    #[arg(long, global = true)]
+    no_config: bool,
+
+    #[command(flatten)]
+    list: ListArgs,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// List files whose content contradicts their extension. Exits with
+    /// status 1 when any are found.
+    CheckMismatch(ScanArgs),
+
+    /// Move files into subdirectories named after their extension. Only
+    /// files directly inside each root are moved unless --max-depth is given.
+    Organize(OrganizeArgs),
+
+    /// Rename files whose extension is not in canonical form, e.g. .JPEG
+    /// to .jpg or .Yml to .yaml. Configured aliases extend the built-in ones.
+    Normalize(NormalizeArgs),
+
+    /// Find files with identical content, grouped by extension, and report
+    /// the bytes reclaimable per extension.
+    Dupes(DupesArgs),
+
+    /// Scan a directory, then keep watching it and print a line for every
+    /// file added to or removed from an extension group (e.g.,
+    /// "+ pdf: report.pdf"). Runs until interrupted.
+    Watch(ScanArgs),
+
+    /// Watch a directory such as a downloads folder and move each file that
+    /// arrives into the folder its extension's rule names, once it has
+    /// stopped changing. Moves are journaled per session. Runs until
+    /// interrupted.
+    Daemon(DaemonArgs),
+
+    /// Save the full index of a directory as JSON, for comparing later with
+    /// `fext diff`.
+    Snapshot(SnapshotArgs),
+
+    /// Compare two snapshots and report the files added, removed or changed
+    /// in each extension group.
+    Diff(DiffArgs),
+
+    /// Reverse the moves recorded in an organize, normalize or daemon
+    /// journal.
+    Undo(UndoArgs),
+
+    /// Manage the cache of directory listings, detected types and hashes
+    /// that lets repeated scans skip unchanged directories.
+    Cache(CacheArgs),
+}
+
+/// Options shared by every command that walks a directory tree.
+#[derive(Args, Debug)]
+struct ScanArgs {
+    /// Directories to scan. Defaults to the current directory.
+    #[arg(value_name = "PATH")]
+    paths: Vec<PathBuf>,
+
+    /// Descend at most this many directory levels below the root
+    /// (1 lists only the files directly inside it).
+    #[arg(long, value_name = "N")]
+    max_depth: Option<usize>,
+
+    /// Only list files found at least this many levels below the root.
+    #[arg(long, value_name = "N", default_value_t = 1)]
+    min_depth: usize,
+
+    /// Include hidden files (names starting with '.').
+    #[arg(long)]
+    hidden: bool,
+
+    /// Only list hidden files.
+    #[arg(long, conflicts_with = "hidden")]
+    hidden_only: bool,
+
+    /// Descend into hidden directories such as .git. This is the default
+    /// with --hidden or --hidden-only.
+    #[arg(long, overrides_with = "no_hidden_dirs")]
+    hidden_dirs: bool,
+
+    /// Never descend into hidden directories.
+    #[arg(long, overrides_with = "hidden_dirs")]
+    no_hidden_dirs: bool,
+
+    /// Don't honor .gitignore, .ignore, .fextignore or .git/info/exclude.
+    #[arg(long)]
+    no_ignore: bool,
+
+    /// Only include files whose path relative to the root matches GLOB.
+    /// May be repeated.
+    #[arg(long, value_name = "GLOB")]
+    include: Vec<String>,
+
+    /// Exclude files and directories whose path relative to the root
+    /// matches GLOB. May be repeated.
+    #[arg(long, value_name = "GLOB")]
+    exclude: Vec<String>,
+
+    /// Only include files with these extensions (e.g., rs,toml).
+    #[arg(long, value_name = "EXT", value_delimiter = ',')]
+    ext: Vec<String>,
+
+    /// Exclude files with these extensions (e.g., log,tmp).
+    #[arg(long, value_name = "EXT", value_delimiter = ',')]
+    not_ext: Vec<String>,
+
+    /// Group known multi-part extensions such as tar.gz or d.ts as a whole
+    /// instead of by their last part.
+    #[arg(long)]
+    compound: bool,
+
+    /// Additional multi-part extensions to recognize (e.g., tar.br);
+    /// implies --compound.
+    #[arg(long, value_name = "EXT", value_delimiter = ',')]
+    compound_ext: Vec<String>,
+
+    /// Group by the extension exactly as written instead of lowercasing it,
+    /// so that .JPG and .jpg are listed separately.
+    #[arg(long)]
+    case_sensitive: bool,
+
+    /// Walk directories on N threads. Defaults to one per CPU; 1 walks
+    /// sequentially.
+    #[arg(long, value_name = "N")]
+    threads: Option<NonZeroUsize>,
+
+    /// Read every directory and file afresh, neither using nor updating
+    /// the scan cache.
+    #[arg(long)]
+    no_cache: bool,
+}
+
+/// Options for the default listing command.
+#[derive(Args, Debug)]
+struct ListArgs {
+    #[command(flatten)]
+    scan: ScanArgs,
+
+    /// How to determine each file's type [default: extension].
+    #[arg(long, value_enum)]
+    detect: Option<DetectMode>,
+
+    /// Output format [default: text].
+    #[arg(long, value_enum)]
+    format: Option<Format>,
+
+    /// Print a per-extension size and count summary instead of the file
+    /// lists. Supports the text and json formats.
+    #[arg(long)]
+    stats: bool,
+
+    /// List extensions written in more than one case (e.g., .jpg and .JPG)
+    /// with the files using each, instead of the file lists. Supports the
+    /// text and json formats.
+    #[arg(long, conflicts_with_all = ["stats", "group_by"])]
+    report_case_variants: bool,
+
+    /// Group by extension, or nest extensions under categories. Grouping
+    /// by category supports the text and json formats [default: extension].
+    #[arg(long, value_enum)]
+    group_by: Option<GroupBy>,
+}
+
+/// The listing options after applying configuration defaults.
+struct Listing {
+    format: Format,
+    stats: bool,
+    case_variants: bool,
+    by_category: bool,
+}
+
+/// Options for the organize command.
+#[derive(Args, Debug)]
+struct OrganizeArgs {
+    #[command(flatten)]
+    scan: ScanArgs,
+
+    /// Folder that receives files without an extension.
+    #[arg(long, value_name = "NAME", default_value = organize::DEFAULT_NO_EXTENSION_DIR)]
+    no_extension_dir: String,
+
+    /// Print the planned moves without touching anything.
+    #[arg(long)]
+    dry_run: bool,
+}
+
+/// Options for the normalize command.
+#[derive(Args, Debug)]
+struct NormalizeArgs {
+    #[command(flatten)]
+    scan: ScanArgs,
+
+    /// Print the planned renames without touching anything.
+    #[arg(long)]
+    dry_run: bool,
+}
+
+/// Options for the dupes command.
+#[derive(Args, Debug)]
+struct DupesArgs {
+    #[command(flatten)]
+    scan: ScanArgs,
+
+    /// Output format. Supports text and json.
+    #[arg(long, value_enum, default_value_t)]
+    format: Format,
+}
+
+/// Options for the snapshot command.
+#[derive(Args, Debug)]
+struct SnapshotArgs {
+    #[command(flatten)]
+    scan: ScanArgs,
+
+    /// Write the snapshot to FILE instead of stdout.
+    #[arg(short, long, value_name = "FILE")]
+    output: Option<PathBuf>,
+}
+
+/// Options for the diff command.
+#[derive(Args, Debug)]
+struct DiffArgs {
+    /// The older snapshot.
+    #[arg(value_name = "OLD")]
+    old: PathBuf,
+
+    /// The newer snapshot.
+    #[arg(value_name = "NEW")]
+    new: PathBuf,
+
+    /// Output format. Supports text and json.
+    #[arg(long, value_enum, default_value_t)]
+    format: Format,
+}
+
+/// Options for the daemon command.
+#[derive(Args, Debug)]
+struct DaemonArgs {
+    /// Directory to watch. Defaults to daemon.dir from the configuration.
+    #[arg(value_name = "DIR")]
+    dir: Option<PathBuf>,
+
+    /// Move files with extension EXT into DEST (e.g., pdf=Documents).
+    /// Relative destinations are inside the watched directory. May be
+    /// repeated; adds to the rules in the configuration.
+    #[arg(long = "rule", value_name = "EXT=DEST", value_parser = parse_rule)]
+    rules: Vec<(String, PathBuf)>,
+
+    /// How long a file's size and modification time must stay the same
+    /// before it is moved [default: 5s].
+    #[arg(long, value_name = "DURATION", value_parser = humantime::parse_duration)]
+    settle: Option<Duration>,
+
+    /// Include hidden files (names starting with '.').
+    #[arg(long)]
+    hidden: bool,
+
+    /// Don't honor .gitignore, .ignore, .fextignore or .git/info/exclude.
+    #[arg(long)]
+    no_ignore: bool,
+}
+
+/// Parses a daemon rule of the form "EXT=DEST".
+fn parse_rule(rule: &str) -> Result<(String, PathBuf), String> {
+    match rule.split_once('=') {
+        Some((extension, destination)) if !extension.is_empty() && !destination.is_empty() => {
+            Ok((extension.to_string(), PathBuf::from(destination)))
+        }
+        _ => Err(format!("expected EXT=DEST, got {rule:?}")),
+    }
+}
+
+/// Options for the undo command.
+#[derive(Args, Debug)]
+struct UndoArgs {
+    /// Journal to undo, or a directory whose most recent journal is undone.
+    #[arg(value_name = "JOURNAL", default_value = ".")]
+    journal: PathBuf,
+
+    /// Also move back files that were modified since the run.
+    #[arg(long)]
+    force: bool,
+}
+
+/// Options for the cache command.
+#[derive(Args, Debug)]
+struct CacheArgs {
+    #[command(subcommand)]
+    command: CacheCommand,
+}
+
+#[derive(Subcommand, Debug)]
+enum CacheCommand {
+    /// Drop cached entries for directories and files that changed or no
+    /// longer exist, and the caches of directory trees that are gone.
+    Prune,
+}
+
+impl ScanArgs {
+    /// Builds the scanner configured by the command-line flags, falling back
+    /// to the defaults in `config`.
+    fn scanner(&self, config: &Config) -> Result<Scanner, globset::Error> {
+        let defaults = &config.defaults;
+        let hidden = if self.hidden_only {
+            Hidden::Only
+        } else if self.hidden || defaults.hidden == Some(true) {
+            Hidden::Include
+        } else {
+            Hidden::Skip
+        };
+
+        // Hidden directories are entered whenever hidden files are wanted,
+        // unless overridden either way.
+        let hidden_dirs = if self.no_hidden_dirs {
+            false
+        } else {
+            self.hidden_dirs || defaults.hidden_dirs.unwrap_or(hidden != Hidden::Skip)
+        };
+        let compound =
+            self.compound || !self.compound_ext.is_empty() || defaults.compound == Some(true);
+
+        let mut filter = Filter::new().exclude_extensions(&self.not_ext);
+        if !self.include.is_empty() {
+            filter = filter.include(glob_set(&self.include)?);
+        }
+        if !self.exclude.is_empty() {
+            filter = filter.exclude(glob_set(&self.exclude)?);
+        }
+        if !self.ext.is_empty() {
+            filter = filter.extensions(&self.ext);
+        }
+
+        let scanner = Scanner::new()
+            .max_depth(self.max_depth.or(defaults.max_depth))
+            .min_depth(self.min_depth)
+            .hidden(hidden)
+            .hidden_dirs(hidden_dirs)
+            .ignore_files(!self.no_ignore && defaults.ignore != Some(false))
+            .filter(filter)
+            .compound(compound)
+            .threads(self.threads.map(NonZeroUsize::get))
+            .cache_dir(self.cache_dir())
+            .case_sensitive(self.case_sensitive || defaults.case_sensitive == Some(true))
+            .compound_extensions(self.compound_ext.iter().cloned());
+        Ok(config.configure(scanner))
+    }
+
+    /// Where the scan cache is kept, unless disabled.
+    fn cache_dir(&self) -> Option<PathBuf> {
+        if self.no_cache {
+            None
+        } else {
+            cache::default_dir()
+        }
+    }
+
+    /// The roots to scan, falling back to the current directory when none
+    /// were given.
+    fn roots(&self) -> io::Result<Vec<PathBuf>> {
+        if self.paths.is_empty() {
+            Ok(vec![env::current_dir()?])
+        } else {
+            Ok(self.paths.clone())
+        }
+    }
+}
+
+/// Compiles the `patterns` given on the command line into one set.
+fn glob_set(patterns: &[String]) -> Result<GlobSet, globset::Error> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(Glob::new(pattern)?);
+    }
+    builder.build()
+}
+
+/// Takes `flag` from the command line, else parses the configured default
+/// for the option `name`, else uses the built-in default.
+fn resolve<T: ValueEnum + Default>(
+    flag: Option<T>,
+    configured: Option<&str>,
+    name: &str,
+) -> Result<T, String> {
+    match (flag, configured) {
+        (Some(value), _) => Ok(value),
+        (None, Some(value)) => T::from_str(value, true)
+            .map_err(|_| format!("invalid {name} {value:?} in configuration")),
+        (None, None) => Ok(T::default()),
+    }
+}
+
+/// Scans the tree under `root` and writes its files grouped by extension.
+/// For the json format the document is returned instead, so that the caller
+/// can gather the documents of several roots.
+fn run_file_sorter(
+    root: &Path,
+    scanner: &Scanner,
+    listing: &Listing,
+    taxonomy: &Taxonomy,
+    first: bool,
+    out: &mut impl Write,
+) -> Result<Option<Value>, Box<dyn std::error::Error>> {
+    let index = scanner.scan(root)?;
+    warn_unreadable(&index);
+    let by_category = listing.by_category;
+
+    if listing.case_variants {
+        match listing.format {
+            Format::Text => report::write_case_variants(&index, out)?,
+            Format::Json => return Ok(Some(report::case_variants_json_document(&index)?)),
+            Format::Csv | Format::Tsv => unreachable!("rejected by run_list"),
+        }
+        return Ok(None);
+    }
+
+    if listing.stats {
+        match (listing.format, by_category) {
+            (Format::Text, false) => report::write_stats(&index, out)?,
+            (Format::Text, true) => report::write_category_stats(&index, taxonomy, out)?,
+            (Format::Json, false) => return Ok(Some(report::stats_json_document(&index)?)),
+            (Format::Json, true) => {
+                return Ok(Some(report::category_stats_json_document(
+                    &index, taxonomy,
+                )?));
             }
+            (Format::Csv | Format::Tsv, _) => unreachable!("rejected by run_list"),
+        }
+        return Ok(None);
+    }
+
+    if by_category {
+        match listing.format {
+            Format::Text => report::write_categories(&index, taxonomy, out)?,
+            Format::Json => {
+                return Ok(Some(report::categories_json_document(&index, taxonomy)?));
+            }
+            Format::Csv | Format::Tsv => unreachable!("rejected by run_list"),
+        }
+        return Ok(None);
+    }
+
+    // Tabular formats share a single header row across all roots.
+    match listing.format {
+        Format::Text => report::write_text(&index, out)?,
+        Format::Json => return Ok(Some(report::json_document(&index)?)),
+        Format::Csv => report::write_csv(&index, first, out)?,
+        Format::Tsv => report::write_tsv(&index, first, out)?,
+    }
+
+    Ok(None)
+}
+
+/// Warns on stderr about each directory that a scan had to leave out.
+fn warn_unreadable(index: &ExtensionIndex) {
+    for dir in index.unreadable_dirs() {
+        eprintln!(
+            "Warning: skipped unreadable directory {}",
+            escape_path(&index.root().join(dir))
+        );
+    }
+}
+
+/// Writes the JSON `documents` of the scanned roots, pretty-printed and
+/// followed by a newline: the document itself for a single root, or an
+/// array of them, since documents written one after another are not JSON.
+fn write_json_documents(documents: &[Value], out: &mut impl Write) -> io::Result<()> {
+    match documents {
+        [document] => serde_json::to_writer_pretty(&mut *out, document)?,
+        documents => serde_json::to_writer_pretty(&mut *out, documents)?,
+    }
+    writeln!(out)
+}
+
+/// Scans every root given on the command line. Each root is reported on
+/// separately, in a single JSON array for the json format.
+fn run_list(args: &ListArgs, config: &Config) -> Result<ExitCode, Box<dyn std::error::Error>> {
+    let defaults = &config.defaults;
+    let listing = Listing {
+        format: resolve(args.format, defaults.format.as_deref(), "format")?,
+        stats: args.stats,
+        case_variants: args.report_case_variants,
+        by_category: resolve(args.group_by, defaults.group_by.as_deref(), "group_by")?
+            == GroupBy::Category,
+    };
+    let detect: DetectMode = resolve(args.detect, defaults.detect.as_deref(), "detect")?;
+
+    if matches!(listing.format, Format::Csv | Format::Tsv) {
+        if listing.stats {
+            return Err("--stats supports only the text and json formats".into());
+        }
+        if listing.case_variants {
+            return Err("--report-case-variants supports only the text and json formats".into());
+        }
+        if listing.by_category {
+            return Err("--group-by category supports only the text and json formats".into());
         }
     }
 
-    // 6. Print the results.
-    for (extension, filenames) in files_by_extension {
-        // Since BTreeMap iterates in sorted key order (by extension), we only
-        // need to sort the filenames within each group.
-        let mut sorted_filenames = filenames;
-        sorted_filenames.sort_unstable(); // Use unstable sort for efficiency
+    let taxonomy = config.taxonomy();
+
+    let scanner = args.scan.scanner(config)?.detect(detect.into());
+    let mut out = BufWriter::new(io::stdout().lock());
 
-        // Print the extension header (e.g., "pdf:").
-        println!("{extension}:");
+    let mut documents = Vec::new();
+    for (i, root) in args.scan.roots()?.iter().enumerate() {
+        let document = run_file_sorter(root, &scanner, &listing, &taxonomy, i == 0, &mut out)
+            .map_err(|e| format!("failed to scan {}: {e}", root.display()))?;
+        documents.extend(document);
+    }
 
-        // Print the list of files.
-        for filename in sorted_filenames {
-            println!("- {filename}");
+    if matches!(listing.format, Format::Json) {
+        write_json_documents(&documents, &mut out)?;
+    }
+    out.flush()?;
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Reports files whose content contradicts their extension, failing when
+/// any are found so the command can gate CI jobs or uploads.
+fn run_check_mismatch(
+    args: &ScanArgs,
+    config: &Config,
+) -> Result<ExitCode, Box<dyn std::error::Error>> {
+    let scanner = args.scanner(config)?.detect(Detect::Content);
+    let mut out = BufWriter::new(io::stdout().lock());
+    let mut mismatches = 0;
+
+    for root in args.roots()? {
+        let index = scanner
+            .scan(&root)
+            .map_err(|e| format!("failed to scan {}: {e}", root.display()))?;
+        warn_unreadable(&index);
+        mismatches += report::write_mismatches(&index, &mut out)?;
+    }
+
+    out.flush()?;
+    Ok(if mismatches > 0 {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    })
+}
+
+/// Reports the files under each root that have identical content.
+fn run_dupes(args: &DupesArgs, config: &Config) -> Result<ExitCode, Box<dyn std::error::Error>> {
+    if matches!(args.format, Format::Csv | Format::Tsv) {
+        return Err("dupes supports only the text and json formats".into());
+    }
+
+    let scanner = args.scan.scanner(config)?;
+    let mut out = BufWriter::new(io::stdout().lock());
+    let mut documents = Vec::new();
+    for root in args.scan.roots()? {
+        let index = scanner
+            .scan(&root)
+            .map_err(|e| format!("failed to scan {}: {e}", root.display()))?;
+        warn_unreadable(&index);
+        let cache = match args.scan.cache_dir() {
+            Some(dir) => Some(
+                Cache::open(dir, &root)
+                    .map_err(|e| format!("failed to scan {}: {e}", root.display()))?,
+            ),
+            None => None,
+        };
+        let duplicates = Duplicates::find(&index, cache.as_ref())
+            .map_err(|e| format!("failed to read {}: {e}", root.display()))?;
+        // A cache that cannot be written only makes the next run slower.
+        if let Some(cache) = &cache {
+            let _ = cache.save();
+        }
+        match args.format {
+            Format::Text => report::write_duplicates(&index, &duplicates, &mut out)?,
+            Format::Json => documents.push(report::duplicates_json_document(&index, &duplicates)?),
+            Format::Csv | Format::Tsv => unreachable!("rejected above"),
         }
-        println!(); // Add a blank line for clean separation between groups.
     }
 
+    if matches!(args.format, Format::Json) {
+        write_json_documents(&documents, &mut out)?;
+    }
+
+    out.flush()?;
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Watches a single root and prints each change to its index as it happens.
+fn run_watch(args: &ScanArgs, config: &Config) -> Result<ExitCode, Box<dyn std::error::Error>> {
+    let root = match args.roots()?.as_slice() {
+        [root] => root.clone(),
+        _ => return Err("watch takes a single directory".into()),
+    };
+
+    let mut live = LiveIndex::new(args.scanner(config)?, &root)
+        .map_err(|e| format!("failed to scan {}: {e}", root.display()))?;
+    warn_unreadable(live.index());
+    println!("Scanning directory: {}\n", escape_path(&root));
+    println!(
+        "{} file(s) in {} extension group(s). Watching for changes...\n",
+        live.index().file_count(),
+        live.index().len()
+    );
+
+    live.watch(|changes| {
+        let mut out = io::stdout().lock();
+        report::write_changes(changes, &mut out)?;
+        out.flush()
+    })
+    .map_err(|e| format!("failed to watch {}: {e}", root.display()))?;
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Scans a single root and saves its index.
+fn run_snapshot(
+    args: &SnapshotArgs,
+    config: &Config,
+) -> Result<ExitCode, Box<dyn std::error::Error>> {
+    let root = match args.scan.roots()?.as_slice() {
+        [root] => root.clone(),
+        _ => return Err("snapshot takes a single directory".into()),
+    };
+
+    let index = args
+        .scan
+        .scanner(config)?
+        .scan(&root)
+        .map_err(|e| format!("failed to scan {}: {e}", root.display()))?;
+
+    warn_unreadable(&index);
+    let snapshot = Snapshot::new(&index);
+
+    match &args.output {
+        Some(path) => snapshot
+            .save(path)
+            .map_err(|e| format!("failed to write {}: {e}", path.display()))?,
+        None => {
+            let mut out = BufWriter::new(io::stdout().lock());
+            snapshot.write(&mut out)?;
+            out.flush()?;
+        }
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Reports how the inventory changed between two snapshots.
+fn run_diff(args: &DiffArgs) -> Result<ExitCode, Box<dyn std::error::Error>> {
+    if matches!(args.format, Format::Csv | Format::Tsv) {
+        return Err("diff supports only the text and json formats".into());
+    }
+
+    let load = |path: &Path| {
+        Snapshot::load(path).map_err(|e| format!("failed to read snapshot {}: {e}", path.display()))
+    };
+    let (old, new) = (load(&args.old)?, load(&args.new)?);
+    let diffs = snapshot::diff(&old, &new);
+
+    let mut out = BufWriter::new(io::stdout().lock());
+    match args.format {
+        Format::Text => report::write_diff(&old, &new, &diffs, &mut out)?,
+        Format::Json => report::write_diff_json(&old, &new, &diffs, &mut out)?,
+        Format::Csv | Format::Tsv => unreachable!("rejected above"),
+    }
+    out.flush()?;
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Moves files arriving in the watched directory according to the rules,
+/// printing each move as it happens.
+fn run_daemon(args: &DaemonArgs, config: &Config) -> Result<ExitCode, Box<dyn std::error::Error>> {
+    let daemon_config = &config.daemon;
+    let root = args
+        .dir
+        .clone()
+        .or_else(|| daemon_config.dir.clone())
+        .ok_or("no directory to watch; pass one or set daemon.dir in the configuration")?;
+
+    let mut rules = Rules::new();
+    for (extension, destination) in daemon_config.rules.iter().chain(
+        args.rules
+            .iter()
+            .map(|(extension, destination)| (extension, destination)),
+    ) {
+        rules = rules.rule(extension, destination);
+    }
+    if rules.is_empty() {
+        return Err(
+            "no rules; pass --rule EXT=DEST or set daemon.rules in the configuration".into(),
+        );
+    }
+
+    let settle = match (args.settle, &daemon_config.settle) {
+        (Some(settle), _) => settle,
+        (None, Some(settle)) => humantime::parse_duration(settle)
+            .map_err(|e| format!("invalid daemon.settle {settle:?} in configuration: {e}"))?,
+        (None, None) => daemon::DEFAULT_SETTLE,
+    };
+
+    let hidden = args.hidden || config.defaults.hidden == Some(true);
+    let scanner = config.configure(
+        Scanner::new()
+            .hidden(if hidden {
+                Hidden::Include
+            } else {
+                Hidden::Skip
+            })
+            .ignore_files(!args.no_ignore && config.defaults.ignore != Some(false))
+            .compound(config.defaults.compound == Some(true))
+            .case_sensitive(config.defaults.case_sensitive == Some(true)),
+    );
+
+    // Finish any session that was interrupted mid-move.
+    resume_journals(&root)?;
+
+    let mut daemon = Daemon::new(scanner, &root, rules)
+        .map_err(|e| format!("failed to watch {}: {e}", root.display()))?
+        .settle(settle);
+    println!("Watching directory: {}\n", escape_path(&root));
+
+    daemon
+        .run(|planned, outcome| {
+            print_outcome(planned, outcome, false);
+            io::stdout().flush()
+        })
+        .map_err(|e| format!("failed to watch {}: {e}", root.display()))?;
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Prints what happened to each move of an organize run, or of its undo.
+fn print_outcomes(outcomes: &[(Move, Outcome)], undo: bool) {
+    for (planned, outcome) in outcomes {
+        print_outcome(planned, outcome, undo);
+    }
+}
+
+/// Prints what happened to a single move, or to its undo.
+fn print_outcome(planned: &Move, outcome: &Outcome, undo: bool) {
+    let (from, to) = if undo {
+        (
+            escape_path(&planned.destination),
+            escape_path(&planned.source),
+        )
+    } else {
+        (
+            escape_path(&planned.source),
+            escape_path(&planned.destination),
+        )
+    };
+
+    match outcome {
+        Outcome::Moved => println!("Moved {from} -> {to}"),
+        Outcome::AlreadyMoved => println!("Moved {from} -> {to} (before interruption)"),
+        Outcome::Conflict => println!("Skipped {from}: {to} already exists"),
+        Outcome::Missing => println!("Missing {from}: no longer exists"),
+        Outcome::Modified => {
+            println!("Modified {from}: changed since the run, left in place (use --force)")
+        }
+        Outcome::Failed(error) => println!("Failed {from} -> {to}: {error}"),
+    }
+}
+
+/// Finishes any run under `root` that was interrupted.
+fn resume_journals(root: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    for path in journal::list(root)? {
+        let mut journal = Journal::open(&path)?;
+        if !journal.is_complete() {
+            println!("Resuming interrupted run from {}", path.display());
+            print_outcomes(&journal.run()?, false);
+        }
+    }
+    Ok(())
+}
+
+/// Applies `plan`, or only prints it with `dry_run`, then lists the moves
+/// skipped because of conflicts. `command` names the journal.
+fn run_plan(plan: &Plan, command: &str, dry_run: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let root = plan.root();
+    if dry_run {
+        for planned in plan.moves() {
+            println!(
+                "Would move {} -> {}",
+                escape_path(&planned.source),
+                escape_path(&planned.destination)
+            );
+        }
+    } else if !plan.moves().is_empty() {
+        let journal_path = journal::new_path(root, command);
+        let outcomes = plan
+            .apply(&journal_path)
+            .map_err(|e| format!("failed to {command} {}: {e}", root.display()))?;
+        print_outcomes(&outcomes, false);
+        println!("Journal: {}", journal_path.display());
+    }
+
+    for conflict in plan.conflicts() {
+        println!(
+            "Skipped {}: {} is already taken",
+            escape_path(&conflict.source),
+            escape_path(&conflict.destination)
+        );
+    }
+    println!();
     Ok(())
 }
 
-fn main() {
-    match run_file_sorter() {
-        Ok(()) => {}
+/// Moves the files under each root into per-extension folders, or only
+/// prints the plan with --dry-run. Every run is journaled so it can be
+/// resumed after a crash and reversed with `fext undo`.
+fn run_organize(
+    args: &OrganizeArgs,
+    config: &Config,
+) -> Result<ExitCode, Box<dyn std::error::Error>> {
+    // Organizing is usually meant for a single flat folder, so default to
+    // the files directly inside each root.
+    let scanner = args
+        .scan
+        .scanner(config)?
+        .max_depth(args.scan.max_depth.or(Some(1)));
+
+    for root in args.scan.roots()? {
+        println!("Organizing directory: {}\n", escape_path(&root));
+
+        // Finish any run that was interrupted before planning new moves.
+        if !args.dry_run {
+            resume_journals(&root)?;
+        }
+
+        let index = scanner
+            .scan(&root)
+            .map_err(|e| format!("failed to scan {}: {e}", root.display()))?;
+
+        warn_unreadable(&index);
+        run_plan(
+            &Plan::new(&index, &args.no_extension_dir),
+            "organize",
+            args.dry_run,
+        )?;
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Renames files under each root whose extension is not in canonical form,
+/// or only prints the renames with --dry-run. Runs are journaled like
+/// organize runs and can be reversed with `fext undo`.
+fn run_normalize(
+    args: &NormalizeArgs,
+    config: &Config,
+) -> Result<ExitCode, Box<dyn std::error::Error>> {
+    // Keys must be the extensions as written, so the configured aliases go
+    // into the canonical form instead of the scan.
+    let scanner = args
+        .scan
+        .scanner(config)?
+        .case_sensitive(true)
+        .aliases(Vec::<(String, String)>::new());
+    let canonical = Canonical::builtin().aliases(&config.aliases);
+
+    for root in args.scan.roots()? {
+        println!("Normalizing directory: {}\n", escape_path(&root));
+
+        if !args.dry_run {
+            resume_journals(&root)?;
+        }
+
+        let index = scanner
+            .scan(&root)
+            .map_err(|e| format!("failed to scan {}: {e}", root.display()))?;
+
+        warn_unreadable(&index);
+        run_plan(
+            &normalize::plan(&index, &canonical),
+            "normalize",
+            args.dry_run,
+        )?;
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Reverses the moves recorded in a journal. Exits with status 1 if any
+/// file could not be restored.
+fn run_undo(args: &UndoArgs) -> Result<ExitCode, Box<dyn std::error::Error>> {
+    // A directory stands for the most recent journal of the run there.
+    let path = if args.journal.is_dir() {
+        journal::list(&args.journal)?
+            .pop()
+            .ok_or_else(|| format!("no journal found in {}", args.journal.display()))?
+    } else {
+        args.journal.clone()
+    };
+
+    let mut journal = Journal::open(&path)
+        .map_err(|e| format!("failed to read journal {}: {e}", path.display()))?;
+    println!(
+        "Undoing {} in {}\n",
+        path.display(),
+        journal.root().display()
+    );
+
+    let outcomes = journal.undo(args.force)?;
+    print_outcomes(&outcomes, true);
+
+    let failed = outcomes
+        .iter()
+        .filter(|(_, outcome)| *outcome != Outcome::Moved)
+        .count();
+    if failed > 0 {
+        println!("\n{failed} file(s) could not be restored.");
+        return Ok(ExitCode::FAILURE);
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Maintains the scan cache.
+fn run_cache(args: &CacheArgs) -> Result<ExitCode, Box<dyn std::error::Error>> {
+    let dir = cache::default_dir().ok_or("no cache directory: HOME is not set")?;
+
+    match args.command {
+        CacheCommand::Prune => {
+            let pruned = cache::prune(&dir)
+                .map_err(|e| format!("failed to prune {}: {e}", dir.display()))?;
+            println!(
+                "Removed {} stale entr(ies) and {} cache file(s), freeing {}.",
+                pruned.entries,
+                pruned.caches,
+                stats::format_size(pruned.bytes as f64)
+            );
+        }
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+fn run(cli: &Cli) -> Result<ExitCode, Box<dyn std::error::Error>> {
+    let mut config = if cli.no_config {
+        Config::default()
+    } else {
+        Config::discover(&env::current_dir()?)
+            .map_err(|e| format!("failed to read configuration: {e}"))?
+    };
+    if let Some(path) = &cli.config {
+        config.merge(
+            Config::load(path)
+                .map_err(|e| format!("failed to read configuration {}: {e}", path.display()))?,
+        );
+    }
+
+    match &cli.command {
+        None => run_list(&cli.list, &config),
+        Some(Command::CheckMismatch(args)) => run_check_mismatch(args, &config),
+        Some(Command::Organize(args)) => run_organize(args, &config),
+        Some(Command::Normalize(args)) => run_normalize(args, &config),
+        Some(Command::Dupes(args)) => run_dupes(args, &config),
+        Some(Command::Watch(args)) => run_watch(args, &config),
+        Some(Command::Daemon(args)) => run_daemon(args, &config),
+        Some(Command::Snapshot(args)) => run_snapshot(args, &config),
+        Some(Command::Diff(args)) => run_diff(args),
+        Some(Command::Undo(args)) => run_undo(args),
+        Some(Command::Cache(args)) => run_cache(args),
+    }
+}
+
+/// Parses the command line. The listing options are rejected alongside a
+/// subcommand by hand rather than by clap, so that the global `--config`
+/// and `--no-config` are still accepted before one.
+fn parse_cli() -> Cli {
+    let mut command = Cli::command();
+    let matches = command.get_matches_mut();
+
+    if matches.subcommand().is_some() {
+        let listing = matches.ids().find(|id| {
+            !matches!(id.as_str(), "config" | "no_config")
+                && matches.value_source(id.as_str()) == Some(ValueSource::CommandLine)
+        });
+        if let Some(id) = listing {
+            let name = command
+                .get_arguments()
+                .find(|arg| arg.get_id() == id)
+                .and_then(|arg| arg.get_long())
+                .map_or_else(|| id.to_string(), |long| format!("--{long}"));
+            command
+                .error(
+                    ErrorKind::ArgumentConflict,
+                    format!("{name} cannot be used with a subcommand"),
+                )
+                .exit();
+        }
+    }
+
+    Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit())
+}
+
+fn main() -> ExitCode {
+    let cli = parse_cli();
+
+    match run(&cli) {
+        Ok(code) => code,
         Err(e) => {
             // Print errors to stderr and exit with a non-zero status code.
             eprintln!("An error occurred: {e}");
-            std::process::exit(1);
+            ExitCode::FAILURE
         }
     }
 }
@@ -0,0 +1,136 @@
+//! Renaming files so their extensions take a canonical form.
+
+use std::collections::HashMap;
+
+use crate::ExtensionIndex;
+use crate::extension::normalize;
+use crate::journal::JOURNAL_DIR;
+use crate::organize::{Move, Plan};
+
+/// Extensions renamed to another by default, as `(alias, canonical)` pairs.
+/// Configured aliases take precedence; mapping an extension to itself keeps
+/// it as it is.
+pub const BUILTIN_ALIASES: &[(&str, &str)] = &[
+    ("jpeg", "jpg"),
+    ("jpe", "jpg"),
+    ("jfif", "jpg"),
+    ("tif", "tiff"),
+    ("yml", "yaml"),
+    ("htm", "html"),
+    ("markdown", "md"),
+    ("mkd", "md"),
+    ("text", "txt"),
+    ("mpeg", "mpg"),
+];
+
+/// Extensions that mean the same in any case, so that e.g. ".PDF" is safely
+/// renamed ".pdf". Any other extension may be case-significant (".C" is C++
+/// where ".c" is C, ".S" is assembly to preprocess where ".s" is not) and is
+/// only renamed through an alias.
+pub const CASE_INSENSITIVE: &[&str] = &[
+    "jpg", "jpeg", "jpe", "jfif", "png", "gif", "bmp", "tif", "tiff", "webp", "heic", "heif",
+    "avif", "svg", "ico", "psd", "cr2", "nef", "dng", "mp3", "wav", "flac", "aac", "ogg", "oga",
+    "opus", "m4a", "wma", "aiff", "mp4", "m4v", "mov", "avi", "mkv", "wmv", "webm", "mpg", "mpeg",
+    "3gp", "flv", "pdf", "doc", "docx", "xls", "xlsx", "ppt", "pptx", "odt", "ods", "odp", "rtf",
+    "txt", "text", "csv", "tsv", "md", "markdown", "mkd", "epub", "html", "htm", "xml", "json",
+    "yaml", "yml", "toml", "ini", "log", "eml", "vcf", "ics", "zip", "rar", "7z", "gz", "tgz",
+    "bz2", "xz", "zst", "tar", "iso", "dmg", "exe", "msi", "ttf", "otf", "woff", "woff2",
+];
+
+/// The canonical form of every extension: aliases resolved, and lowercase
+/// where the case carries no meaning.
+#[derive(Debug, Clone)]
+pub struct Canonical {
+    aliases: HashMap<String, String>,
+}
+
+impl Default for Canonical {
+    fn default() -> Self {
+        Self::builtin()
+    }
+}
+
+impl Canonical {
+    /// Resolves the [`BUILTIN_ALIASES`] and lowercases the
+    /// [`CASE_INSENSITIVE`] extensions.
+    pub fn builtin() -> Self {
+        Self {
+            aliases: BUILTIN_ALIASES
+                .iter()
+                .map(|&(alias, canonical)| (alias.to_string(), canonical.to_string()))
+                .collect(),
+        }
+    }
+
+    /// Adds the `aliases` as `(alias, canonical)` pairs, replacing any
+    /// existing alias of the same extension.
+    pub fn aliases<I, A, C>(mut self, aliases: I) -> Self
+    where
+        I: IntoIterator<Item = (A, C)>,
+        A: AsRef<str>,
+        C: AsRef<str>,
+    {
+        for (alias, canonical) in aliases {
+            self.aliases
+                .insert(normalize(alias.as_ref()), normalize(canonical.as_ref()));
+        }
+        self
+    }
+
+    /// The canonical form of `extension`. Aliases match in any case; other
+    /// extensions are lowercased only if every part of them is in
+    /// [`CASE_INSENSITIVE`], and otherwise kept as written.
+    pub fn of(&self, extension: &str) -> String {
+        let lowercase = extension.to_lowercase();
+        if let Some(canonical) = self.aliases.get(&lowercase) {
+            canonical.clone()
+        } else if lowercase
+            .split('.')
+            .all(|part| CASE_INSENSITIVE.contains(&part))
+        {
+            lowercase
+        } else {
+            extension.to_string()
+        }
+    }
+}
+
+/// Plans renaming every file in `index` whose extension differs from its
+/// canonical form, keeping it in its directory (e.g., "IMG_1.JPEG" becomes
+/// "IMG_1.jpg").
+///
+/// The index must have been scanned case-sensitively and without aliases,
+/// so that each file's key is its extension as written. Files without an
+/// extension, or whose name is not valid UTF-8, are left alone.
+pub fn plan(index: &ExtensionIndex, canonical: &Canonical) -> Plan {
+    let mut candidates = Vec::new();
+
+    for file in index.files() {
+        // Never rename fext's own journals.
+        if file.path().starts_with(JOURNAL_DIR) || file.path().extension().is_none() {
+            continue;
+        }
+        let Some(name) = file.file_name().to_str() else {
+            continue;
+        };
+
+        // The key is the written extension, including any compound part.
+        let written = file.extension();
+        let Some(stem) = name.strip_suffix(written) else {
+            continue;
+        };
+        let extension = canonical.of(written);
+        if extension == written {
+            continue;
+        }
+
+        candidates.push(Move {
+            source: file.path().to_path_buf(),
+            destination: file.path().with_file_name(format!("{stem}{extension}")),
+            size: file.size(),
+            modified: file.modified(),
+        });
+    }
+
+    Plan::from_moves(index.root(), candidates)
+}
@@ -0,0 +1,140 @@
+//! Moving files into per-extension folders.
+
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::ExtensionIndex;
+use crate::journal::{JOURNAL_DIR, Journal, Outcome, is_taken};
+
+/// Default folder for files without an extension, since their
+/// [`NO_EXTENSION_PLACEHOLDER`] key makes a poor directory name.
+///
+/// [`NO_EXTENSION_PLACEHOLDER`]: crate::NO_EXTENSION_PLACEHOLDER
+pub const DEFAULT_NO_EXTENSION_DIR: &str = "no-extension";
+
+/// A single planned move, with both paths relative to the plan's root.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Move {
+    pub source: PathBuf,
+    pub destination: PathBuf,
+    /// Size of the source when the move was planned.
+    pub size: u64,
+    /// Modification time of the source when the move was planned.
+    pub modified: Option<SystemTime>,
+}
+
+/// The moves needed to organize a scanned tree, computed up front so they
+/// can be previewed before anything on disk changes.
+#[derive(Debug, Clone)]
+pub struct Plan {
+    root: PathBuf,
+    moves: Vec<Move>,
+    conflicts: Vec<Move>,
+}
+
+impl Plan {
+    /// Plans moving every file in `index` into `<root>/<extension>/`, with
+    /// extensionless files going to `no_extension_dir` instead. Files already
+    /// in place are left alone.
+    pub fn new(index: &ExtensionIndex, no_extension_dir: &str) -> Self {
+        let mut candidates = Vec::new();
+
+        for (extension, files) in index {
+            let folder = if extension == index.no_extension_key() {
+                no_extension_dir
+            } else {
+                extension
+            };
+
+            for file in files {
+                // Never move fext's own journals, which a scan including
+                // hidden directories may pick up.
+                if file.path().starts_with(JOURNAL_DIR) {
+                    continue;
+                }
+
+                let destination = Path::new(folder).join(file.file_name());
+                if destination == file.path() {
+                    continue;
+                }
+
+                candidates.push(Move {
+                    source: file.path().to_path_buf(),
+                    destination,
+                    size: file.size(),
+                    modified: file.modified(),
+                });
+            }
+        }
+
+        Self::from_moves(index.root(), candidates)
+    }
+
+    /// Plans the `candidates` under `root`, setting aside as conflicts those
+    /// that would overwrite something or whose destination folder is taken
+    /// by a file.
+    pub(crate) fn from_moves(root: &Path, candidates: impl IntoIterator<Item = Move>) -> Self {
+        let mut moves = Vec::new();
+        let mut conflicts = Vec::new();
+
+        // Destinations claimed by earlier moves in this plan.
+        let mut claimed = HashSet::new();
+
+        for planned in candidates {
+            // Never overwrite: a destination that already exists, or that
+            // another file in this run is headed for, is a conflict.
+            if is_taken(
+                &root.join(&planned.source),
+                &root.join(&planned.destination),
+            ) || is_blocked(root, &planned.destination)
+                || !claimed.insert(planned.destination.clone())
+            {
+                conflicts.push(planned);
+            } else {
+                moves.push(planned);
+            }
+        }
+
+        Self {
+            root: root.to_path_buf(),
+            moves,
+            conflicts,
+        }
+    }
+
+    /// The directory the plan's paths are relative to.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Moves that will be performed, in the order they were planned.
+    pub fn moves(&self) -> &[Move] {
+        &self.moves
+    }
+
+    /// Moves skipped because their destination is already taken, or could
+    /// not be created because a file is in the way.
+    pub fn conflicts(&self) -> &[Move] {
+        &self.conflicts
+    }
+
+    /// Records the planned moves in a new journal at `journal_path`, then
+    /// performs them, creating destination folders as needed. If this is
+    /// interrupted, [`Journal::run`] on the same journal finishes the job.
+    pub fn apply(&self, journal_path: &Path) -> io::Result<Vec<(Move, Outcome)>> {
+        Journal::create(journal_path, &self.root, &self.moves)?.run()
+    }
+}
+
+/// Whether a folder on the way to `destination` under `root` exists as
+/// something other than a directory, so that the move could only fail.
+fn is_blocked(root: &Path, destination: &Path) -> bool {
+    destination
+        .ancestors()
+        .skip(1)
+        .filter(|folder| !folder.as_os_str().is_empty())
+        .any(|folder| fs::metadata(root.join(folder)).is_ok_and(|metadata| !metadata.is_dir()))
+}
@@ -0,0 +1,882 @@
+use std::io::{self, Write};
+use std::time::SystemTime;
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::case::CaseVariants;
+use crate::category::Taxonomy;
+use crate::dupes::{DuplicateSet, Duplicates, Reclaimable};
+use crate::encoding::{SerPath, base64, escape, escape_path};
+use crate::snapshot::{GroupDiff, Snapshot};
+use crate::stats::{Stats, Summary, format_size};
+use crate::watch::Change;
+use crate::{Detect, ExtensionIndex, FileEntry};
+
+/// Writes `index` in the human-readable layout: a "Scanning directory:"
+/// header, then each extension (e.g., "pdf:") followed by its files.
+pub fn write_text(index: &ExtensionIndex, mut out: impl Write) -> io::Result<()> {
+    // Print the directory being scanned for context.
+    writeln!(out, "Scanning directory: {}\n", escape_path(index.root()))?;
+
+    for (extension, files) in index {
+        // Print the extension header (e.g., "pdf:").
+        writeln!(out, "{extension}:")?;
+
+        write_files(extension, files, "", &mut out)?;
+        writeln!(out)?; // Add a blank line for clean separation between groups.
+    }
+
+    write_non_utf8_count(index, &mut out)
+}
+
+/// Writes `index` like [`write_text`], but with the extension groups nested
+/// under the category `taxonomy` places them in (e.g., "images:").
+pub fn write_categories(
+    index: &ExtensionIndex,
+    taxonomy: &Taxonomy,
+    mut out: impl Write,
+) -> io::Result<()> {
+    writeln!(out, "Scanning directory: {}\n", escape_path(index.root()))?;
+
+    for category in taxonomy.categorize(index) {
+        writeln!(out, "{}:", category.name)?;
+        for (extension, files) in &category.groups {
+            writeln!(out, "  {extension}:")?;
+            write_files(extension, files, "  ", &mut out)?;
+        }
+        writeln!(out)?;
+    }
+
+    write_non_utf8_count(index, &mut out)
+}
+
+/// Writes the "- file" lines of the group `extension`, each prefixed by
+/// `indent`.
+fn write_files(
+    extension: &str,
+    files: &[FileEntry],
+    indent: &str,
+    mut out: impl Write,
+) -> io::Result<()> {
+    // Note the declared extension of any file that was grouped by its
+    // content under a different key.
+    for file in files {
+        write!(out, "{indent}- {}", escape_path(file.path()))?;
+        if file.extension() != extension {
+            write!(out, " (declared: {})", file.extension())?;
+        }
+        writeln!(out)?;
+    }
+
+    Ok(())
+}
+
+/// Notes how many file names had to be escaped, if any.
+fn write_non_utf8_count(index: &ExtensionIndex, mut out: impl Write) -> io::Result<()> {
+    match index.non_utf8_count() {
+        0 => Ok(()),
+        count => writeln!(
+            out,
+            "{count} file(s) with non-UTF-8 names, shown escaped.\n"
+        ),
+    }
+}
+
+/// Writes a table with the file count, total, mean and median size, share
+/// of all bytes and largest file of every extension group, ending with a
+/// grand total.
+pub fn write_stats(index: &ExtensionIndex, out: impl Write) -> io::Result<()> {
+    write_stats_table(index, &Stats::new(index), "extension", out)
+}
+
+/// Writes the table of [`write_stats`] with one row per category of
+/// `taxonomy` instead of per extension.
+pub fn write_category_stats(
+    index: &ExtensionIndex,
+    taxonomy: &Taxonomy,
+    out: impl Write,
+) -> io::Result<()> {
+    write_stats_table(index, &Stats::by_category(index, taxonomy), "category", out)
+}
+
+fn write_stats_table(
+    index: &ExtensionIndex,
+    stats: &Stats,
+    label: &str,
+    mut out: impl Write,
+) -> io::Result<()> {
+    writeln!(out, "Scanning directory: {}\n", escape_path(index.root()))?;
+
+    let total_bytes = stats.total.total_bytes;
+
+    // Render every cell first so the columns can be sized to fit.
+    let row = |label: &str, summary: &Summary| {
+        [
+            label.to_string(),
+            summary.count.to_string(),
+            format_size(summary.total_bytes as f64),
+            format_size(summary.mean_bytes),
+            format_size(summary.median_bytes),
+            format!("{:.1}%", summary.percent_of(total_bytes)),
+            summary
+                .largest
+                .map(|file| escape_path(file.path()).into_owned())
+                .unwrap_or_default(),
+        ]
+    };
+
+    let header = [
+        label, "files", "total", "mean", "median", "share", "largest",
+    ]
+    .map(String::from);
+    let mut rows = vec![header];
+    rows.extend(
+        stats
+            .groups
+            .iter()
+            .map(|(extension, summary)| row(extension, summary)),
+    );
+    rows.push(row("total", &stats.total));
+
+    let mut widths = [0; 7];
+    for cells in &rows {
+        for (width, cell) in widths.iter_mut().zip(cells) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    for cells in &rows {
+        // The extension is left-aligned, numbers right-aligned and the
+        // trailing largest-file column is left unpadded.
+        write!(out, "{:<w$}", cells[0], w = widths[0])?;
+        for (cell, width) in cells[1..6].iter().zip(&widths[1..6]) {
+            write!(out, "  {cell:>width$}")?;
+        }
+        writeln!(out, "  {}", cells[6])?;
+    }
+    writeln!(out)?;
+
+    write_non_utf8_count(index, &mut out)
+}
+
+/// Writes the files in `index` whose content contradicts their extension
+/// and returns how many there were. The index must have been built with
+/// content detection for any mismatch to be found.
+pub fn write_mismatches(index: &ExtensionIndex, mut out: impl Write) -> io::Result<usize> {
+    writeln!(out, "Scanning directory: {}\n", escape_path(index.root()))?;
+
+    let mut count = 0;
+    for file in index.files() {
+        if let Some(content_type) = file.mismatch() {
+            writeln!(
+                out,
+                "- {}: declared {}, content is {}",
+                escape_path(file.path()),
+                file.extension(),
+                content_type.name
+            )?;
+            count += 1;
+        }
+    }
+
+    if count == 0 {
+        writeln!(out, "No mismatched extensions found.")?;
+    } else {
+        writeln!(out, "\n{count} file(s) with mismatched extensions.")?;
+    }
+    writeln!(out)?;
+
+    Ok(count)
+}
+
+/// Writes every extension of `index` that appears in more than one casing,
+/// with the files using each casing.
+pub fn write_case_variants(index: &ExtensionIndex, mut out: impl Write) -> io::Result<()> {
+    writeln!(out, "Scanning directory: {}\n", escape_path(index.root()))?;
+
+    let found = CaseVariants::find(index);
+    for variants in &found {
+        writeln!(out, "{}:", variants.extension)?;
+        for (written, files) in &variants.variants {
+            writeln!(out, "  {written}:")?;
+            for file in files {
+                writeln!(out, "  - {}", escape_path(file.path()))?;
+            }
+        }
+        writeln!(out)?;
+    }
+
+    if found.is_empty() {
+        writeln!(out, "No extensions with case variants found.\n")?;
+    } else {
+        writeln!(
+            out,
+            "{} extension(s) written in more than one case.\n",
+            found.len()
+        )?;
+    }
+
+    write_non_utf8_count(index, &mut out)?;
+    Ok(())
+}
+
+/// Writes the duplicate sets under the extension of their original, noting
+/// sets whose copies have different extensions, followed by a table of the
+/// bytes reclaimable per extension by deleting all copies but the first.
+pub fn write_duplicates(
+    index: &ExtensionIndex,
+    duplicates: &Duplicates,
+    mut out: impl Write,
+) -> io::Result<()> {
+    writeln!(out, "Scanning directory: {}\n", escape_path(index.root()))?;
+
+    if duplicates.groups.is_empty() {
+        writeln!(out, "No duplicate files found.\n")?;
+        return write_non_utf8_count(index, &mut out);
+    }
+
+    for (extension, sets) in &duplicates.groups {
+        writeln!(out, "{extension}:")?;
+        for set in sets {
+            write!(
+                out,
+                "- {} identical files, {} each",
+                set.files.len(),
+                format_size(set.size as f64)
+            )?;
+            if set.has_mixed_extensions() {
+                write!(
+                    out,
+                    ", different extensions ({})",
+                    set.extensions().join(", ")
+                )?;
+            }
+            writeln!(out, ":")?;
+            for file in &set.files {
+                writeln!(out, "  - {}", escape_path(file.path()))?;
+            }
+        }
+        writeln!(out)?;
+    }
+
+    // Render every cell first so the columns can be sized to fit.
+    let row = |label: &str, reclaimable: &Reclaimable| {
+        [
+            label.to_string(),
+            reclaimable.count.to_string(),
+            format_size(reclaimable.bytes as f64),
+        ]
+    };
+    let mut rows = vec![["extension", "copies", "reclaimable"].map(String::from)];
+    rows.extend(
+        duplicates
+            .reclaimable
+            .iter()
+            .map(|(extension, reclaimable)| row(extension, reclaimable)),
+    );
+    rows.push(row("total", &duplicates.total_reclaimable()));
+
+    let mut widths = [0; 3];
+    for cells in &rows {
+        for (width, cell) in widths.iter_mut().zip(cells) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    for [label, count, bytes] in &rows {
+        writeln!(
+            out,
+            "{label:<w0$}  {count:>w1$}  {bytes:>w2$}",
+            w0 = widths[0],
+            w1 = widths[1],
+            w2 = widths[2]
+        )?;
+    }
+    writeln!(out)?;
+
+    write_non_utf8_count(index, &mut out)
+}
+
+/// Writes one line per change of a watched index, e.g. "+ pdf: report.pdf"
+/// for an added file and "- pdf: report.pdf" for a removed one.
+pub fn write_changes(changes: &[Change], mut out: impl Write) -> io::Result<()> {
+    for change in changes {
+        let (sign, extension, file) = match change {
+            Change::Added(extension, file) => ('+', extension, file),
+            Change::Removed(extension, file) => ('-', extension, file),
+        };
+        writeln!(out, "{sign} {extension}: {}", escape_path(file.path()))?;
+    }
+    Ok(())
+}
+
+/// Writes how each extension group changed from `old` to `new`: a header
+/// with the net file count and byte deltas (e.g., "pdf: +2 file(s),
+/// +1.5 MiB"), then "+ file" for added, "- file" for removed and "~ file"
+/// for changed files.
+pub fn write_diff(
+    old: &Snapshot,
+    new: &Snapshot,
+    diffs: &[GroupDiff],
+    mut out: impl Write,
+) -> io::Result<()> {
+    writeln!(
+        out,
+        "Comparing {} ({}) with {} ({})\n",
+        escape_path(&old.root),
+        format_time(old.created),
+        escape_path(&new.root),
+        format_time(new.created)
+    )?;
+
+    for group in diffs {
+        writeln!(
+            out,
+            "{}: {:+} file(s), {}",
+            group.extension,
+            group.count_delta,
+            format_delta(group.bytes_delta)
+        )?;
+        for file in &group.added {
+            writeln!(out, "+ {}", escape_path(&file.path))?;
+        }
+        for file in &group.removed {
+            writeln!(out, "- {}", escape_path(&file.path))?;
+        }
+        for (before, after) in &group.changed {
+            writeln!(
+                out,
+                "~ {} ({} -> {})",
+                escape_path(&after.path),
+                format_size(before.size as f64),
+                format_size(after.size as f64)
+            )?;
+        }
+        writeln!(out)?;
+    }
+
+    if diffs.is_empty() {
+        writeln!(out, "No differences found.\n")?;
+    }
+    Ok(())
+}
+
+/// Formats a change in size with its sign, e.g. "+1.5 MiB" or "-20 B".
+fn format_delta(bytes: i64) -> String {
+    let sign = if bytes < 0 { '-' } else { '+' };
+    format!("{sign}{}", format_size(bytes.unsigned_abs() as f64))
+}
+
+/// JSON layout of a scanned root. Groups are a list rather than an object so
+/// that their sorted order survives any JSON parser.
+#[derive(Serialize)]
+struct JsonReport<'a> {
+    root: SerPath<'a>,
+    file_count: usize,
+    non_utf8_count: usize,
+    groups: Vec<JsonGroup<'a>>,
+}
+
+#[derive(Serialize)]
+struct JsonGroup<'a> {
+    extension: &'a str,
+    count: usize,
+    files: Vec<JsonFile<'a>>,
+}
+
+impl<'a> JsonGroup<'a> {
+    fn new(index: &ExtensionIndex, extension: &'a str, files: &'a [FileEntry]) -> Self {
+        Self {
+            extension,
+            count: files.len(),
+            files: files
+                .iter()
+                .map(|file| JsonFile::new(file, index.detect()))
+                .collect(),
+        }
+    }
+}
+
+/// Files are plain paths unless content detection ran, in which case each
+/// also carries its declared extension and detected type.
+#[derive(Serialize)]
+#[serde(untagged)]
+enum JsonFile<'a> {
+    Path(SerPath<'a>),
+    Detected {
+        path: SerPath<'a>,
+        declared: &'a str,
+        detected: Option<&'static str>,
+    },
+}
+
+impl<'a> JsonFile<'a> {
+    fn new(file: &'a FileEntry, detect: Detect) -> Self {
+        match detect {
+            Detect::Extension => Self::Path(SerPath(file.path())),
+            Detect::Content => Self::Detected {
+                path: SerPath(file.path()),
+                declared: file.extension(),
+                detected: file.detected().map(|content_type| content_type.name),
+            },
+        }
+    }
+}
+
+/// Builds the JSON document for `index`: its root, counts and files grouped
+/// by extension.
+pub fn json_document(index: &ExtensionIndex) -> serde_json::Result<Value> {
+    let report = JsonReport {
+        root: SerPath(index.root()),
+        file_count: index.file_count(),
+        non_utf8_count: index.non_utf8_count(),
+        groups: index
+            .iter()
+            .map(|(extension, files)| JsonGroup::new(index, extension, files))
+            .collect(),
+    };
+
+    serde_json::to_value(report)
+}
+
+#[derive(Serialize)]
+struct JsonCategories<'a> {
+    root: SerPath<'a>,
+    file_count: usize,
+    non_utf8_count: usize,
+    categories: Vec<JsonCategory<'a>>,
+}
+
+#[derive(Serialize)]
+struct JsonCategory<'a> {
+    category: String,
+    count: usize,
+    groups: Vec<JsonGroup<'a>>,
+}
+
+/// Builds the JSON document for `index` like [`json_document`], with the
+/// extension groups nested under their categories.
+pub fn categories_json_document(
+    index: &ExtensionIndex,
+    taxonomy: &Taxonomy,
+) -> serde_json::Result<Value> {
+    let report = JsonCategories {
+        root: SerPath(index.root()),
+        file_count: index.file_count(),
+        non_utf8_count: index.non_utf8_count(),
+        categories: taxonomy
+            .categorize(index)
+            .into_iter()
+            .map(|category| JsonCategory {
+                count: category.file_count(),
+                groups: category
+                    .groups
+                    .iter()
+                    .map(|(extension, files)| JsonGroup::new(index, extension, files))
+                    .collect(),
+                category: category.name,
+            })
+            .collect(),
+    };
+
+    serde_json::to_value(report)
+}
+
+#[derive(Serialize)]
+struct JsonCaseReport<'a> {
+    root: SerPath<'a>,
+    non_utf8_count: usize,
+    extensions: Vec<JsonCaseVariants<'a>>,
+}
+
+#[derive(Serialize)]
+struct JsonCaseVariants<'a> {
+    extension: String,
+    count: usize,
+    variants: Vec<JsonCaseVariant<'a>>,
+}
+
+#[derive(Serialize)]
+struct JsonCaseVariant<'a> {
+    extension: String,
+    count: usize,
+    files: Vec<SerPath<'a>>,
+}
+
+/// Builds the JSON document for the extensions of [`write_case_variants`].
+pub fn case_variants_json_document(index: &ExtensionIndex) -> serde_json::Result<Value> {
+    let found = CaseVariants::find(index);
+    let report = JsonCaseReport {
+        root: SerPath(index.root()),
+        non_utf8_count: index.non_utf8_count(),
+        extensions: found
+            .into_iter()
+            .map(|variants| JsonCaseVariants {
+                count: variants.file_count(),
+                extension: variants.extension,
+                variants: variants
+                    .variants
+                    .into_iter()
+                    .map(|(written, files)| JsonCaseVariant {
+                        extension: written,
+                        count: files.len(),
+                        files: files.into_iter().map(|file| SerPath(file.path())).collect(),
+                    })
+                    .collect(),
+            })
+            .collect(),
+    };
+
+    serde_json::to_value(report)
+}
+
+#[derive(Serialize)]
+struct JsonDuplicates<'a> {
+    root: SerPath<'a>,
+    non_utf8_count: usize,
+    groups: Vec<JsonDuplicateGroup<'a>>,
+    reclaimable: Vec<JsonReclaimable<'a>>,
+    total: JsonReclaimable<'a>,
+}
+
+#[derive(Serialize)]
+struct JsonDuplicateGroup<'a> {
+    extension: &'a str,
+    sets: Vec<JsonDuplicateSet<'a>>,
+}
+
+#[derive(Serialize)]
+struct JsonDuplicateSet<'a> {
+    size: u64,
+    hash: &'a str,
+    mixed_extensions: bool,
+    extensions: Vec<&'a str>,
+    files: Vec<SerPath<'a>>,
+}
+
+impl<'a> JsonDuplicateSet<'a> {
+    fn new(set: &'a DuplicateSet) -> Self {
+        Self {
+            size: set.size,
+            hash: &set.hash,
+            mixed_extensions: set.has_mixed_extensions(),
+            extensions: set.extensions(),
+            files: set.files.iter().map(|file| SerPath(file.path())).collect(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct JsonReclaimable<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    extension: Option<&'a str>,
+    count: usize,
+    bytes: u64,
+}
+
+impl<'a> JsonReclaimable<'a> {
+    fn new(extension: Option<&'a str>, reclaimable: Reclaimable) -> Self {
+        Self {
+            extension,
+            count: reclaimable.count,
+            bytes: reclaimable.bytes,
+        }
+    }
+}
+
+/// Builds the JSON document for the duplicates of [`write_duplicates`].
+/// Sizes are in bytes.
+pub fn duplicates_json_document(
+    index: &ExtensionIndex,
+    duplicates: &Duplicates,
+) -> serde_json::Result<Value> {
+    let report = JsonDuplicates {
+        root: SerPath(index.root()),
+        non_utf8_count: index.non_utf8_count(),
+        groups: duplicates
+            .groups
+            .iter()
+            .map(|(extension, sets)| JsonDuplicateGroup {
+                extension,
+                sets: sets.iter().map(JsonDuplicateSet::new).collect(),
+            })
+            .collect(),
+        reclaimable: duplicates
+            .reclaimable
+            .iter()
+            .map(|(extension, reclaimable)| JsonReclaimable::new(Some(extension), *reclaimable))
+            .collect(),
+        total: JsonReclaimable::new(None, duplicates.total_reclaimable()),
+    };
+
+    serde_json::to_value(report)
+}
+
+#[derive(Serialize)]
+struct JsonDiff<'a> {
+    old: JsonDiffSide<'a>,
+    new: JsonDiffSide<'a>,
+    groups: Vec<JsonGroupDiff<'a>>,
+}
+
+#[derive(Serialize)]
+struct JsonDiffSide<'a> {
+    root: SerPath<'a>,
+    created: String,
+}
+
+impl<'a> JsonDiffSide<'a> {
+    fn new(snapshot: &'a Snapshot) -> Self {
+        Self {
+            root: SerPath(&snapshot.root),
+            created: format_time(snapshot.created),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct JsonGroupDiff<'a> {
+    extension: &'a str,
+    count_delta: i64,
+    bytes_delta: i64,
+    added: Vec<SerPath<'a>>,
+    removed: Vec<SerPath<'a>>,
+    changed: Vec<JsonChangedFile<'a>>,
+}
+
+#[derive(Serialize)]
+struct JsonChangedFile<'a> {
+    path: SerPath<'a>,
+    old_size: u64,
+    new_size: u64,
+}
+
+/// Writes the differences of [`write_diff`] as a pretty-printed JSON
+/// document followed by a newline. Sizes are in bytes.
+pub fn write_diff_json(
+    old: &Snapshot,
+    new: &Snapshot,
+    diffs: &[GroupDiff],
+    mut out: impl Write,
+) -> io::Result<()> {
+    let report = JsonDiff {
+        old: JsonDiffSide::new(old),
+        new: JsonDiffSide::new(new),
+        groups: diffs
+            .iter()
+            .map(|group| JsonGroupDiff {
+                extension: group.extension,
+                count_delta: group.count_delta,
+                bytes_delta: group.bytes_delta,
+                added: group.added.iter().map(|file| SerPath(&file.path)).collect(),
+                removed: group
+                    .removed
+                    .iter()
+                    .map(|file| SerPath(&file.path))
+                    .collect(),
+                changed: group
+                    .changed
+                    .iter()
+                    .map(|(before, after)| JsonChangedFile {
+                        path: SerPath(&after.path),
+                        old_size: before.size,
+                        new_size: after.size,
+                    })
+                    .collect(),
+            })
+            .collect(),
+    };
+
+    serde_json::to_writer_pretty(&mut out, &report)?;
+    writeln!(out)
+}
+
+#[derive(Serialize)]
+struct JsonStats<'a> {
+    root: SerPath<'a>,
+    non_utf8_count: usize,
+    groups: Vec<JsonSummary<'a>>,
+    total: JsonSummary<'a>,
+}
+
+#[derive(Serialize)]
+struct JsonSummary<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    extension: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    category: Option<&'a str>,
+    count: usize,
+    total_bytes: u64,
+    mean_bytes: f64,
+    median_bytes: f64,
+    percent: f64,
+    largest: Option<SerPath<'a>>,
+}
+
+impl<'a> JsonSummary<'a> {
+    fn new(summary: &Summary<'a>, total_bytes: u64) -> Self {
+        Self {
+            extension: None,
+            category: None,
+            count: summary.count,
+            total_bytes: summary.total_bytes,
+            mean_bytes: summary.mean_bytes,
+            median_bytes: summary.median_bytes,
+            percent: summary.percent_of(total_bytes),
+            largest: summary.largest.map(|file| SerPath(file.path())),
+        }
+    }
+}
+
+/// Builds the JSON document for the statistics of [`write_stats`]. Sizes are
+/// in bytes.
+pub fn stats_json_document(index: &ExtensionIndex) -> serde_json::Result<Value> {
+    stats_document(index, &Stats::new(index), false)
+}
+
+/// Builds the JSON document for the statistics of [`write_category_stats`].
+/// Sizes are in bytes.
+pub fn category_stats_json_document(
+    index: &ExtensionIndex,
+    taxonomy: &Taxonomy,
+) -> serde_json::Result<Value> {
+    stats_document(index, &Stats::by_category(index, taxonomy), true)
+}
+
+fn stats_document(
+    index: &ExtensionIndex,
+    stats: &Stats,
+    by_category: bool,
+) -> serde_json::Result<Value> {
+    let total_bytes = stats.total.total_bytes;
+
+    let report = JsonStats {
+        root: SerPath(index.root()),
+        non_utf8_count: index.non_utf8_count(),
+        groups: stats
+            .groups
+            .iter()
+            .map(|(label, summary)| {
+                let mut json = JsonSummary::new(summary, total_bytes);
+                if by_category {
+                    json.category = Some(label);
+                } else {
+                    json.extension = Some(label);
+                }
+                json
+            })
+            .collect(),
+        total: JsonSummary::new(&stats.total, total_bytes),
+    };
+
+    serde_json::to_value(report)
+}
+
+/// Writes one row per file as comma-separated values, preceded by a header
+/// row when `header` is set. See [`write_tsv`] for the columns.
+pub fn write_csv(index: &ExtensionIndex, header: bool, out: impl Write) -> io::Result<()> {
+    write_delimited(index, ',', header, out)
+}
+
+/// Writes one row per file as tab-separated values, preceded by a header
+/// row when `header` is set.
+///
+/// The columns are extension, filename, path relative to the root, size in
+/// bytes and modification time (RFC 3339, UTC). With content detection,
+/// declared extension and detected type columns follow. A path_base64 column
+/// holds the raw bytes of any path that is not valid UTF-8, whose filename
+/// and path columns are then escaped. A final root column holds the scanned
+/// root, telling apart the rows of several roots written one after another.
+/// Fields containing the delimiter, a double quote or a line break are
+/// quoted, with embedded quotes doubled.
+pub fn write_tsv(index: &ExtensionIndex, header: bool, out: impl Write) -> io::Result<()> {
+    write_delimited(index, '\t', header, out)
+}
+
+fn write_delimited(
+    index: &ExtensionIndex,
+    delimiter: char,
+    header: bool,
+    mut out: impl Write,
+) -> io::Result<()> {
+    let detect = index.detect() == Detect::Content;
+
+    if header {
+        let mut columns = vec!["extension", "filename", "path", "size", "modified"];
+        if detect {
+            columns.extend(["declared", "detected"]);
+        }
+        columns.extend(["path_base64", "root"]);
+        write_record(&mut out, delimiter, &columns)?;
+    }
+
+    let root = escape_path(index.root());
+    for (extension, files) in index {
+        for file in files {
+            let file_name = escape(file.file_name());
+            let path = escape_path(file.path());
+            let size = file.size().to_string();
+            let modified = file.modified().map(format_time).unwrap_or_default();
+
+            let mut fields = vec![extension, &*file_name, &path, &size, &modified];
+            if detect {
+                fields.push(file.extension());
+                fields.push(file.detected().map_or("", |content_type| content_type.name));
+            }
+            let raw_path = base64(file.path().as_os_str()).unwrap_or_default();
+            fields.extend([&*raw_path, &root]);
+            write_record(&mut out, delimiter, &fields)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Writes a single row, quoting any field that needs it.
+fn write_record(out: &mut impl Write, delimiter: char, fields: &[&str]) -> io::Result<()> {
+    for (i, field) in fields.iter().enumerate() {
+        if i > 0 {
+            write!(out, "{delimiter}")?;
+        }
+
+        let needs_quotes = field.contains([delimiter, '"', '\n', '\r']);
+        if needs_quotes {
+            write!(out, "\"{}\"", field.replace('"', "\"\""))?;
+        } else {
+            write!(out, "{field}")?;
+        }
+    }
+
+    // RFC 4180 specifies CRLF line endings, which spreadsheets also accept.
+    write!(out, "\r\n")
+}
+
+/// Formats `time` as an RFC 3339 timestamp in UTC with second precision.
+fn format_time(time: SystemTime) -> String {
+    humantime::format_rfc3339_seconds(time).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(delimiter: char, fields: &[&str]) -> String {
+        let mut out = Vec::new();
+        write_record(&mut out, delimiter, fields).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn quotes_fields_that_need_it() {
+        let fields = ["plain", "a,b", "a\tb", "say \"hi\"", "two\nlines"];
+
+        assert_eq!(
+            record(',', &fields),
+            "plain,\"a,b\",a\tb,\"say \"\"hi\"\"\",\"two\nlines\"\r\n"
+        );
+        assert_eq!(
+            record('\t', &fields),
+            "plain\ta,b\t\"a\tb\"\t\"say \"\"hi\"\"\"\t\"two\nlines\"\r\n"
+        );
+    }
+}
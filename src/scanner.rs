@@ -0,0 +1,552 @@
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Mutex;
+
+use rayon::prelude::*;
+
+use crate::NO_EXTENSION_PLACEHOLDER;
+use crate::cache::{Cache, Entry};
+use crate::detect::{self, Detect};
+use crate::extension::{COMPOUND_EXTENSIONS, KeyRules};
+use crate::filter::Filter;
+use crate::ignores::IgnoreStack;
+use crate::index::{ExtensionIndex, FileEntry};
+
+/// Which files are recorded, based on whether their name starts with '.'.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Hidden {
+    /// Skip hidden files for cleaner output.
+    #[default]
+    Skip,
+    /// Record hidden files alongside all others.
+    Include,
+    /// Record only hidden files, e.g. to audit dotfile configs.
+    Only,
+}
+
+/// Builder for scanning a directory tree into an [`ExtensionIndex`].
+///
+/// By default hidden files and directories (starting with '.') are skipped,
+/// see [`Scanner::hidden`] and [`Scanner::hidden_dirs`], as is anything
+/// excluded by ignore files, see [`Scanner::ignore_files`].
+#[derive(Debug, Clone)]
+pub struct Scanner {
+    max_depth: Option<usize>,
+    min_depth: usize,
+    detect: Detect,
+    hidden: Hidden,
+    hidden_dirs: bool,
+    ignore_files: bool,
+    filter: Filter,
+    compound: bool,
+    extra_compound: Vec<String>,
+    aliases: Vec<(String, String)>,
+    placeholder: String,
+    case_sensitive: bool,
+    threads: Option<usize>,
+    cache_dir: Option<PathBuf>,
+}
+
+impl Default for Scanner {
+    fn default() -> Self {
+        Self {
+            max_depth: None,
+            min_depth: 1,
+            detect: Detect::Extension,
+            hidden: Hidden::Skip,
+            hidden_dirs: false,
+            ignore_files: true,
+            filter: Filter::new(),
+            compound: false,
+            extra_compound: Vec::new(),
+            aliases: Vec::new(),
+            placeholder: NO_EXTENSION_PLACEHOLDER.to_string(),
+            case_sensitive: false,
+            threads: None,
+            cache_dir: None,
+        }
+    }
+}
+
+impl Scanner {
+    /// Creates a scanner that walks the whole tree.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Descend at most `depth` directory levels below the root; `Some(1)`
+    /// lists only the files directly inside it and `Some(0)` nothing at
+    /// all. `None` means no limit.
+    pub fn max_depth(mut self, depth: Option<usize>) -> Self {
+        self.max_depth = depth;
+        self
+    }
+
+    /// Only record files found at least `depth` levels below the root.
+    pub fn min_depth(mut self, depth: usize) -> Self {
+        self.min_depth = depth;
+        self
+    }
+
+    /// Chooses whether files are grouped by extension or by content.
+    pub fn detect(mut self, detect: Detect) -> Self {
+        self.detect = detect;
+        self
+    }
+
+    /// Chooses whether hidden files are skipped, included or the only ones
+    /// recorded. This does not affect which directories are descended into.
+    pub fn hidden(mut self, hidden: Hidden) -> Self {
+        self.hidden = hidden;
+        self
+    }
+
+    /// Whether to descend into hidden directories such as `.git`.
+    pub fn hidden_dirs(mut self, descend: bool) -> Self {
+        self.hidden_dirs = descend;
+        self
+    }
+
+    /// Whether to honor `.ignore` and `.fextignore` files in the tree and
+    /// its parent directories, plus `.gitignore` files and
+    /// `.git/info/exclude` inside a git repository. Enabled by default.
+    pub fn ignore_files(mut self, enabled: bool) -> Self {
+        self.ignore_files = enabled;
+        self
+    }
+
+    /// Restricts the scan to files accepted by `filter`. Rejected files never
+    /// reach the index.
+    pub fn filter(mut self, filter: Filter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Whether to group files with a known multi-part extension, such as
+    /// "backup.tar.gz", under the whole of it ("tar.gz") rather than only
+    /// the last part. See [`COMPOUND_EXTENSIONS`].
+    pub fn compound(mut self, enabled: bool) -> Self {
+        self.compound = enabled;
+        self
+    }
+
+    /// Additional multi-part extensions to recognize in compound mode.
+    pub fn compound_extensions<I, S>(mut self, extensions: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.extra_compound
+            .extend(extensions.into_iter().map(Into::into));
+        self
+    }
+
+    /// Extension aliases as `(alias, canonical)` pairs: files whose key would
+    /// be the alias are grouped under the canonical key instead (e.g.,
+    /// `("jpeg", "jpg")`). Aliases match in any case, even when
+    /// [case sensitive](Self::case_sensitive), and resolve to the canonical
+    /// key in lowercase.
+    pub fn aliases<I, A, C>(mut self, aliases: I) -> Self
+    where
+        I: IntoIterator<Item = (A, C)>,
+        A: Into<String>,
+        C: Into<String>,
+    {
+        self.aliases = aliases
+            .into_iter()
+            .map(|(alias, canonical)| (alias.into(), canonical.into()))
+            .collect();
+        self
+    }
+
+    /// Key for files without an extension; [`NO_EXTENSION_PLACEHOLDER`] by
+    /// default.
+    pub fn no_extension_placeholder(mut self, placeholder: impl Into<String>) -> Self {
+        self.placeholder = placeholder.into();
+        self
+    }
+
+    /// Group by extensions as written rather than lowercased, so that e.g.
+    /// "photo.JPG" and "photo.jpg" land in different groups.
+    pub fn case_sensitive(mut self, enabled: bool) -> Self {
+        self.case_sensitive = enabled;
+        self
+    }
+
+    /// Walk directories on `threads` threads, or on one per CPU with `None`
+    /// (the default). Directories are handed out by work stealing, and the
+    /// resulting index is ordered the same however many threads are used.
+    pub fn threads(mut self, threads: Option<usize>) -> Self {
+        self.threads = threads;
+        self
+    }
+
+    /// Keep a cache of the tree in `dir` (see [`crate::cache`]), so that
+    /// directories unchanged since an earlier scan are not read again and
+    /// the types of unchanged files are not detected again. `None` (the
+    /// default) disables the cache.
+    pub fn cache_dir(mut self, dir: Option<PathBuf>) -> Self {
+        self.cache_dir = dir;
+        self
+    }
+
+    /// Walks the tree under `root` and groups every file by its extension
+    /// key, or by its detected content type if enabled.
+    pub fn scan(&self, root: impl AsRef<Path>) -> io::Result<ExtensionIndex> {
+        let root = root.as_ref();
+        let keys = self.key_rules();
+
+        let mut index = ExtensionIndex::new(
+            root.to_path_buf(),
+            self.detect,
+            keys.no_extension_key().to_string(),
+        );
+
+        // Ignore rules match against absolute paths, so the walk tracks the
+        // canonical location of each directory alongside the given one.
+        let absolute_root = fs::canonicalize(root)?;
+        let ignores = if self.ignore_files {
+            IgnoreStack::new(&absolute_root)
+        } else {
+            IgnoreStack::disabled()
+        };
+
+        let cache = match &self.cache_dir {
+            Some(dir) => Some(Cache::open(dir, &absolute_root)?),
+            None => None,
+        };
+
+        let walk = Walk {
+            root,
+            absolute_root: &absolute_root,
+            keys: &keys,
+            cache: cache.as_ref(),
+            unreadable: Mutex::default(),
+        };
+        let records = match self.threads {
+            None => self.walk(&walk, root, 1, &ignores)?,
+            Some(threads) => rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .map_err(io::Error::other)?
+                .install(|| self.walk(&walk, root, 1, &ignores))?,
+        };
+
+        // Threads finish in any order, so sorting restores a stable one.
+        for (group, file) in records {
+            index.insert(group, file);
+        }
+        index.sort();
+        index.unreadable_dirs = walk
+            .unreadable
+            .into_inner()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        index.unreadable_dirs.sort();
+
+        // A cache that cannot be written only makes the next scan slower.
+        if let Some(cache) = &cache {
+            let _ = cache.save();
+        }
+        Ok(index)
+    }
+
+    /// Examines the single file at `relative` under `root` the way
+    /// [`Self::scan`] would, returning the group it belongs in and its entry,
+    /// or `None` if a scan would not record it (e.g., because it is hidden,
+    /// ignored, filtered out or gone). This keeps an index up to date without
+    /// rescanning the whole tree.
+    pub fn scan_file(
+        &self,
+        root: impl AsRef<Path>,
+        relative: impl AsRef<Path>,
+    ) -> io::Result<Option<(String, FileEntry)>> {
+        let root = root.as_ref();
+        let relative = relative.as_ref();
+        let keys = self.key_rules();
+        let absolute_root = fs::canonicalize(root)?;
+        let mut ignores = if self.ignore_files {
+            IgnoreStack::new(&absolute_root)
+        } else {
+            IgnoreStack::disabled()
+        };
+        let walk = Walk {
+            root,
+            absolute_root: &absolute_root,
+            keys: &keys,
+            cache: None,
+            unreadable: Mutex::default(),
+        };
+
+        let mut names = Vec::new();
+        for component in relative.components() {
+            match component {
+                Component::Normal(name) => names.push(name),
+                _ => return Ok(None),
+            }
+        }
+        let Some((name, parents)) = names.split_last() else {
+            return Ok(None);
+        };
+
+        // Every directory on the way must be one the walk would enter.
+        ignores.push(&absolute_root);
+        let mut dir = root.to_path_buf();
+        for (i, parent) in parents.iter().enumerate() {
+            dir.push(parent);
+            let is_hidden = parent.as_encoded_bytes().starts_with(b".");
+            let is_dir = fs::symlink_metadata(&dir).is_ok_and(|metadata| metadata.is_dir());
+            if !is_dir
+                || (is_hidden && !self.hidden_dirs)
+                || self.max_depth.is_some_and(|max| i + 1 >= max)
+                || self
+                    .filter
+                    .excludes_dir(&relative.iter().take(i + 1).collect::<PathBuf>())
+                || ignores.is_ignored(&walk.absolute(&dir), true)
+            {
+                return Ok(None);
+            }
+            ignores.push(&walk.absolute(&dir));
+        }
+
+        let path = dir.join(name);
+        let is_hidden = name.as_encoded_bytes().starts_with(b".");
+        let is_dir = fs::symlink_metadata(&path).is_ok_and(|metadata| metadata.is_dir());
+        if is_dir || ignores.is_ignored(&walk.absolute(&path), false) {
+            return Ok(None);
+        }
+
+        Ok(self.record(
+            &walk,
+            &path,
+            relative.to_path_buf(),
+            parents.len() + 1,
+            is_hidden,
+        ))
+    }
+
+    /// The rules for deriving extension keys, as configured.
+    fn key_rules(&self) -> KeyRules {
+        let keys = KeyRules::default()
+            .placeholder(&self.placeholder)
+            .case_sensitive(self.case_sensitive)
+            .aliases(self.aliases.iter().map(|(a, c)| (a.as_str(), c.as_str())));
+        if self.compound {
+            let extra = self.extra_compound.iter().map(String::as_str);
+            keys.compound(COMPOUND_EXTENSIONS.iter().copied().chain(extra))
+        } else {
+            keys
+        }
+    }
+
+    /// Recursively walks `dir`, returning the group and entry of every
+    /// regular file to record. `depth` is the depth of the entries inside
+    /// `dir` (files directly inside the root are at depth 1). Subdirectories
+    /// are walked in parallel.
+    fn walk(
+        &self,
+        walk: &Walk,
+        dir: &Path,
+        depth: usize,
+        ignores: &IgnoreStack,
+    ) -> io::Result<Vec<(String, FileEntry)>> {
+        let ignores = ignores.child(&walk.absolute(dir));
+        let mut records = Vec::new();
+        let mut subdirs = Vec::new();
+
+        let entries = match self.entries(walk, dir) {
+            Ok(entries) => entries,
+            // A directory below the root that cannot be read (e.g.,
+            // lost+found) or vanished mid-scan is left out, rather than
+            // failing the whole scan.
+            Err(e)
+                if depth > 1
+                    && matches!(
+                        e.kind(),
+                        io::ErrorKind::PermissionDenied | io::ErrorKind::NotFound
+                    ) =>
+            {
+                let relative = dir.strip_prefix(walk.root).unwrap_or(dir);
+                walk.unreadable
+                    .lock()
+                    .unwrap_or_else(|poisoned| poisoned.into_inner())
+                    .push(relative.to_path_buf());
+                return Ok(Vec::new());
+            }
+            Err(e) => return Err(e),
+        };
+
+        for entry in entries {
+            let path = dir.join(&entry.name);
+
+            // Names need not be valid UTF-8, so look at their raw bytes.
+            let is_hidden = entry.name.as_os_str().as_encoded_bytes().starts_with(b".");
+            let is_dir = entry.is_dir;
+
+            if ignores.is_ignored(&walk.absolute(&path), is_dir) {
+                continue;
+            }
+
+            // Record the path relative to the scan root
+            // (e.g., "<root>/docs/report.pdf" -> "docs/report.pdf").
+            let relative_path = path.strip_prefix(walk.root).map(PathBuf::from);
+            let relative_path = relative_path.unwrap_or_else(|_| path.clone());
+
+            // Descend into subdirectories while within the depth limit. The
+            // entry's own file type is used so that symlinked directories are
+            // not followed, which could otherwise loop forever.
+            if is_dir {
+                if (self.hidden_dirs || !is_hidden)
+                    && self.max_depth.is_none_or(|max| depth < max)
+                    && !self.filter.excludes_dir(&relative_path)
+                {
+                    subdirs.push(path);
+                }
+                continue;
+            }
+
+            if let Some(record) = self.record(walk, &path, relative_path, depth, is_hidden) {
+                records.push(record);
+            }
+        }
+
+        let nested = subdirs
+            .par_iter()
+            .map(|subdir| self.walk(walk, subdir, depth + 1, &ignores))
+            .collect::<io::Result<Vec<_>>>()?;
+        records.extend(nested.into_iter().flatten());
+
+        Ok(records)
+    }
+
+    /// The names in `dir` and whether each is a directory, taken from the
+    /// cache if `dir` has not been modified since it was cached.
+    fn entries(&self, walk: &Walk, dir: &Path) -> io::Result<Vec<Entry>> {
+        let Some(cache) = walk.cache else {
+            return read_entries(dir);
+        };
+
+        let relative = dir.strip_prefix(walk.root).unwrap_or(dir);
+        let modified = fs::metadata(dir)?.modified().ok();
+        if let Some(entries) = cache.listing(relative, modified) {
+            return Ok(entries);
+        }
+        let entries = read_entries(dir)?;
+        cache.store_listing(relative, modified, entries.clone());
+        Ok(entries)
+    }
+
+    /// The group and entry of the non-directory at `path`, found at `depth`,
+    /// if it passes the hidden, depth and filter settings.
+    fn record(
+        &self,
+        walk: &Walk,
+        path: &Path,
+        relative_path: PathBuf,
+        depth: usize,
+        is_hidden: bool,
+    ) -> Option<(String, FileEntry)> {
+        let wanted = match self.hidden {
+            Hidden::Skip => !is_hidden,
+            Hidden::Include => true,
+            Hidden::Only => is_hidden,
+        };
+        if !wanted {
+            return None;
+        }
+
+        // Process only regular files deep enough to be reported. The
+        // metadata follows symlinks, so links to files are included and
+        // broken links are skipped.
+        let metadata = fs::metadata(path).ok()?;
+        if !metadata.is_file()
+            || depth < self.min_depth
+            || self.max_depth.is_some_and(|max| depth > max)
+        {
+            return None;
+        }
+
+        let extension = walk.keys.key(path);
+        if !self.filter.accepts_file(&relative_path, &extension) {
+            return None;
+        }
+
+        let size = metadata.len();
+        let modified = metadata.modified().ok();
+
+        // Unreadable files are simply left undetected rather than
+        // failing the whole scan, and are not cached so that they are
+        // tried again next time.
+        let detected = match self.detect {
+            Detect::Extension => None,
+            Detect::Content => {
+                let cached = walk
+                    .cache
+                    .and_then(|cache| cache.detected(&relative_path, size, modified));
+                match cached {
+                    Some(detected) => detected,
+                    None => match detect::detect_file(path) {
+                        Ok(detected) => {
+                            if let Some(cache) = walk.cache {
+                                cache.store_detected(&relative_path, size, modified, detected);
+                            }
+                            detected
+                        }
+                        Err(_) => None,
+                    },
+                }
+            }
+        };
+
+        let file = FileEntry {
+            path: relative_path,
+            extension,
+            size,
+            modified,
+            detected,
+        };
+
+        // Detected files are grouped under their type's canonical
+        // extension; everything else under the declared one.
+        let group = match detected {
+            Some(content_type) => content_type.canonical_extension().to_string(),
+            None => file.extension.clone(),
+        };
+        Some((group, file))
+    }
+}
+
+/// The root of a walk, as given and as an absolute path, the rules for
+/// keying the files found, the cache of the tree if one is kept, and the
+/// directories that could not be read.
+struct Walk<'a> {
+    root: &'a Path,
+    absolute_root: &'a Path,
+    keys: &'a KeyRules,
+    cache: Option<&'a Cache>,
+    unreadable: Mutex<Vec<PathBuf>>,
+}
+
+impl Walk<'_> {
+    /// The absolute form of `path`, which lies under the given root.
+    fn absolute(&self, path: &Path) -> PathBuf {
+        match path.strip_prefix(self.root) {
+            Ok(relative) => self.absolute_root.join(relative),
+            Err(_) => path.to_path_buf(),
+        }
+    }
+}
+
+/// Reads the names in `dir` and whether each is a directory. Symlinks to
+/// directories count as files, so that they are never followed.
+fn read_entries(dir: &Path) -> io::Result<Vec<Entry>> {
+    fs::read_dir(dir)?
+        .map(|entry| {
+            let entry = entry?;
+            Ok(Entry {
+                name: PathBuf::from(entry.file_name()),
+                is_dir: entry.file_type()?.is_dir(),
+            })
+        })
+        .collect()
+}
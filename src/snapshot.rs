@@ -0,0 +1,181 @@
+//! Saving an extension index to disk and comparing saved indexes.
+
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+
+use crate::ExtensionIndex;
+
+/// Version of the snapshot format written by this build.
+pub const VERSION: u32 = 1;
+
+/// The full inventory of a scanned tree at one point in time.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub version: u32,
+    /// The directory that was scanned.
+    #[serde(with = "crate::encoding::path")]
+    pub root: PathBuf,
+    /// When the snapshot was taken.
+    pub created: SystemTime,
+    /// Groups in sorted order, as a list so that the order survives any
+    /// JSON parser.
+    pub groups: Vec<SnapshotGroup>,
+}
+
+/// The files of one extension group.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotGroup {
+    pub extension: String,
+    pub files: Vec<SnapshotFile>,
+}
+
+/// A file as recorded in a snapshot, relative to its root.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotFile {
+    #[serde(with = "crate::encoding::path")]
+    pub path: PathBuf,
+    pub size: u64,
+    pub modified: Option<SystemTime>,
+}
+
+impl Snapshot {
+    /// Records every file in `index`.
+    pub fn new(index: &ExtensionIndex) -> Self {
+        Self {
+            version: VERSION,
+            root: index.root().to_path_buf(),
+            created: SystemTime::now(),
+            groups: index
+                .iter()
+                .map(|(extension, files)| SnapshotGroup {
+                    extension: extension.to_string(),
+                    files: files
+                        .iter()
+                        .map(|file| SnapshotFile {
+                            path: file.path().to_path_buf(),
+                            size: file.size(),
+                            modified: file.modified(),
+                        })
+                        .collect(),
+                })
+                .collect(),
+        }
+    }
+
+    /// Reads a snapshot from the JSON file at `path`.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let mut snapshot: Self = serde_json::from_reader(BufReader::new(File::open(path)?))?;
+        if snapshot.version > VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unsupported snapshot version {}", snapshot.version),
+            ));
+        }
+
+        // Comparing relies on the order, so restore it in case the file was
+        // edited by hand.
+        for group in &mut snapshot.groups {
+            group
+                .files
+                .sort_by(|a, b| a.path.as_os_str().cmp(b.path.as_os_str()));
+        }
+        Ok(snapshot)
+    }
+
+    /// Writes the snapshot as a pretty-printed JSON document followed by a
+    /// newline.
+    pub fn write(&self, mut out: impl Write) -> io::Result<()> {
+        serde_json::to_writer_pretty(&mut out, self)?;
+        writeln!(out)
+    }
+
+    /// Writes the snapshot to a new or truncated file at `path`.
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut out = BufWriter::new(File::create(path)?);
+        self.write(&mut out)?;
+        out.flush()
+    }
+}
+
+/// How one extension group differs between two snapshots.
+#[derive(Debug, Clone)]
+pub struct GroupDiff<'a> {
+    pub extension: &'a str,
+    /// Files only in the newer snapshot, in path order.
+    pub added: Vec<&'a SnapshotFile>,
+    /// Files only in the older snapshot, in path order.
+    pub removed: Vec<&'a SnapshotFile>,
+    /// Files in both whose size or modification time differ, as
+    /// `(old, new)` pairs in path order.
+    pub changed: Vec<(&'a SnapshotFile, &'a SnapshotFile)>,
+    /// Change in the number of files.
+    pub count_delta: i64,
+    /// Change in the total size of the files, in bytes.
+    pub bytes_delta: i64,
+}
+
+/// The differences between two snapshots, group by group in sorted order.
+/// Groups that did not change are left out. A file that moved to another
+/// group counts as removed from one and added to the other.
+pub fn diff<'a>(old: &'a Snapshot, new: &'a Snapshot) -> Vec<GroupDiff<'a>> {
+    let mut groups: BTreeMap<&str, (&[SnapshotFile], &[SnapshotFile])> = BTreeMap::new();
+    for group in &old.groups {
+        groups.entry(&group.extension).or_default().0 = &group.files;
+    }
+    for group in &new.groups {
+        groups.entry(&group.extension).or_default().1 = &group.files;
+    }
+
+    groups
+        .into_iter()
+        .map(|(extension, (old_files, new_files))| diff_group(extension, old_files, new_files))
+        .filter(|group| {
+            !(group.added.is_empty() && group.removed.is_empty() && group.changed.is_empty())
+        })
+        .collect()
+}
+
+/// Compares the files of one group, merging the two path-sorted lists.
+fn diff_group<'a>(
+    extension: &'a str,
+    old: &'a [SnapshotFile],
+    new: &'a [SnapshotFile],
+) -> GroupDiff<'a> {
+    let total = |files: &[SnapshotFile]| files.iter().map(|file| file.size as i64).sum::<i64>();
+    let mut group = GroupDiff {
+        extension,
+        added: Vec::new(),
+        removed: Vec::new(),
+        changed: Vec::new(),
+        count_delta: new.len() as i64 - old.len() as i64,
+        bytes_delta: total(new) - total(old),
+    };
+
+    let (mut old, mut new) = (old.iter().peekable(), new.iter().peekable());
+    loop {
+        let order = match (old.peek(), new.peek()) {
+            (Some(a), Some(b)) => a.path.as_os_str().cmp(b.path.as_os_str()),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => break,
+        };
+        match order {
+            Ordering::Less => group.removed.extend(old.next()),
+            Ordering::Greater => group.added.extend(new.next()),
+            Ordering::Equal => {
+                let (a, b) = (old.next().unwrap(), new.next().unwrap());
+                if a.size != b.size || a.modified != b.modified {
+                    group.changed.push((a, b));
+                }
+            }
+        }
+    }
+
+    group
+}
@@ -0,0 +1,128 @@
+//! Per-extension size and count statistics.
+
+use crate::category::Taxonomy;
+use crate::{ExtensionIndex, FileEntry};
+
+/// Size statistics for a set of files.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Summary<'a> {
+    /// Number of files.
+    pub count: usize,
+    /// Combined size in bytes.
+    pub total_bytes: u64,
+    /// Mean size in bytes.
+    pub mean_bytes: f64,
+    /// Median size in bytes; the mean of the two middle sizes for an even
+    /// number of files.
+    pub median_bytes: f64,
+    /// The largest file, if there are any files.
+    pub largest: Option<&'a FileEntry>,
+}
+
+impl<'a> Summary<'a> {
+    /// Computes the statistics of `files`.
+    pub fn new(files: impl IntoIterator<Item = &'a FileEntry>) -> Self {
+        let files: Vec<&FileEntry> = files.into_iter().collect();
+
+        let mut sizes: Vec<u64> = files.iter().map(|file| file.size()).collect();
+        sizes.sort_unstable();
+
+        let count = sizes.len();
+        let total_bytes = sizes.iter().sum();
+        let (mean_bytes, median_bytes) = match count {
+            0 => (0.0, 0.0),
+            _ => {
+                let middle = count / 2;
+                let median = if count.is_multiple_of(2) {
+                    (sizes[middle - 1] as f64 + sizes[middle] as f64) / 2.0
+                } else {
+                    sizes[middle] as f64
+                };
+                (total_bytes as f64 / count as f64, median)
+            }
+        };
+
+        // Ties go to the first file in path order.
+        let largest = files.iter().copied().reduce(|largest, file| {
+            if file.size() > largest.size() {
+                file
+            } else {
+                largest
+            }
+        });
+
+        Self {
+            count,
+            total_bytes,
+            mean_bytes,
+            median_bytes,
+            largest,
+        }
+    }
+
+    /// This summary's share of `total_bytes`, as a percentage.
+    pub fn percent_of(&self, total_bytes: u64) -> f64 {
+        if total_bytes == 0 {
+            0.0
+        } else {
+            self.total_bytes as f64 * 100.0 / total_bytes as f64
+        }
+    }
+}
+
+/// Statistics for every group of an index, plus a grand total.
+#[derive(Debug, Clone)]
+pub struct Stats<'a> {
+    /// One summary per extension or category, in sorted order.
+    pub groups: Vec<(String, Summary<'a>)>,
+    /// Summary over all files.
+    pub total: Summary<'a>,
+}
+
+impl<'a> Stats<'a> {
+    /// Computes the statistics of `index`.
+    pub fn new(index: &'a ExtensionIndex) -> Self {
+        Self {
+            groups: index
+                .iter()
+                .map(|(extension, files)| (extension.to_string(), Summary::new(files)))
+                .collect(),
+            total: Summary::new(index.files()),
+        }
+    }
+
+    /// Computes the statistics of `index` per category of `taxonomy`.
+    pub fn by_category(index: &'a ExtensionIndex, taxonomy: &Taxonomy) -> Self {
+        Self {
+            groups: taxonomy
+                .categorize(index)
+                .into_iter()
+                .map(|category| {
+                    let summary = Summary::new(category.files());
+                    (category.name, summary)
+                })
+                .collect(),
+            total: Summary::new(index.files()),
+        }
+    }
+}
+
+/// Formats `bytes` with a binary unit (e.g., "1.5 KiB").
+pub fn format_size(bytes: f64) -> String {
+    const UNITS: [&str; 6] = ["KiB", "MiB", "GiB", "TiB", "PiB", "EiB"];
+
+    if bytes < 1024.0 {
+        return format!("{bytes:.0} B");
+    }
+
+    let mut value = bytes;
+    let mut unit = "B";
+    for next in UNITS {
+        if value < 1024.0 {
+            break;
+        }
+        value /= 1024.0;
+        unit = next;
+    }
+    format!("{value:.1} {unit}")
+}
@@ -0,0 +1,212 @@
+//! Keeping an extension index up to date as files change.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+
+use notify::{EventKind, RecursiveMode, Watcher};
+
+use crate::{ExtensionIndex, FileEntry, Scanner};
+
+/// A file entering or leaving a group of a [`LiveIndex`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    /// The file was recorded under the extension key.
+    Added(String, FileEntry),
+    /// The file was dropped from the extension key.
+    Removed(String, FileEntry),
+}
+
+/// An [`ExtensionIndex`] that is updated file by file instead of rescanned.
+#[derive(Debug)]
+pub struct LiveIndex {
+    scanner: Scanner,
+    index: ExtensionIndex,
+    absolute_root: PathBuf,
+}
+
+impl LiveIndex {
+    /// Scans `root` with `scanner` to build the initial index.
+    pub fn new(scanner: Scanner, root: impl AsRef<Path>) -> io::Result<Self> {
+        let root = root.as_ref();
+        Ok(Self {
+            index: scanner.scan(root)?,
+            absolute_root: fs::canonicalize(root)?,
+            scanner,
+        })
+    }
+
+    /// The index as of the last update.
+    pub fn index(&self) -> &ExtensionIndex {
+        &self.index
+    }
+
+    /// Re-examines `path`, given either relative to the root or as an
+    /// absolute path under it, and updates the index to match what is on
+    /// disk now. A directory is re-examined along with everything below it.
+    /// A file whose group did not change produces no [`Change`].
+    pub fn update(&mut self, path: &Path) -> io::Result<Vec<Change>> {
+        let relative = if path.is_absolute() {
+            match path.strip_prefix(&self.absolute_root) {
+                Ok(relative) => relative.to_path_buf(),
+                Err(_) => return Ok(Vec::new()),
+            }
+        } else {
+            path.to_path_buf()
+        };
+
+        let mut changes = Vec::new();
+        let absolute = self.absolute_root.join(&relative);
+        match fs::symlink_metadata(&absolute) {
+            Ok(metadata) if metadata.is_dir() => {
+                for file in files_under(&absolute, &relative)? {
+                    self.update_file(&file, &mut changes)?;
+                }
+            }
+            Ok(_) => self.update_file(&relative, &mut changes)?,
+            // Whatever was there is gone, including any files below it if it
+            // was a directory.
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                let gone: Vec<PathBuf> = self
+                    .index
+                    .files()
+                    .filter(|file| file.path().starts_with(&relative))
+                    .map(|file| file.path().to_path_buf())
+                    .collect();
+                for path in gone {
+                    if let Some((extension, entry)) = self.index.remove(&path) {
+                        changes.push(Change::Removed(extension, entry));
+                    }
+                }
+            }
+            Err(e) => return Err(e),
+        }
+
+        Ok(changes)
+    }
+
+    /// Scans the whole tree again, for when events may have been lost, and
+    /// returns how the index changed: files that left or moved between
+    /// groups in path order, followed by files that are new.
+    pub fn rescan(&mut self) -> io::Result<Vec<Change>> {
+        let index = self.scanner.scan(self.index.root())?;
+
+        let by_path = |index: &ExtensionIndex| -> BTreeMap<PathBuf, (String, FileEntry)> {
+            index
+                .iter()
+                .flat_map(|(extension, files)| {
+                    files
+                        .iter()
+                        .map(move |file| (file.path.clone(), (extension.to_string(), file.clone())))
+                })
+                .collect()
+        };
+        let old = by_path(&self.index);
+        let mut new = by_path(&index);
+
+        let mut changes = Vec::new();
+        for (path, (extension, entry)) in old {
+            match new.remove(&path) {
+                Some((new_extension, _)) if new_extension == extension => {}
+                Some((new_extension, new_entry)) => {
+                    changes.push(Change::Removed(extension, entry));
+                    changes.push(Change::Added(new_extension, new_entry));
+                }
+                None => changes.push(Change::Removed(extension, entry)),
+            }
+        }
+        changes.extend(
+            new.into_values()
+                .map(|(extension, entry)| Change::Added(extension, entry)),
+        );
+
+        self.index = index;
+        Ok(changes)
+    }
+
+    /// Watches the root for file system events, updating the index and
+    /// passing each batch of changes to `on_change`. Runs until watching
+    /// fails or `on_change` returns an error.
+    pub fn watch(
+        &mut self,
+        mut on_change: impl FnMut(&[Change]) -> io::Result<()>,
+    ) -> io::Result<()> {
+        let (sender, events) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(sender).map_err(io::Error::other)?;
+        watcher
+            .watch(&self.absolute_root, RecursiveMode::Recursive)
+            .map_err(io::Error::other)?;
+
+        for event in events {
+            let event = event.map_err(io::Error::other)?;
+
+            // Reads and opens change nothing the index records.
+            if matches!(event.kind, EventKind::Access(_)) {
+                continue;
+            }
+
+            let changes = if event.need_rescan() {
+                self.rescan()?
+            } else {
+                let mut changes = Vec::new();
+                for path in &event.paths {
+                    changes.extend(self.update(path)?);
+                }
+                changes
+            };
+
+            if !changes.is_empty() {
+                on_change(&changes)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Re-examines the single file at `relative`, recording any change.
+    fn update_file(&mut self, relative: &Path, changes: &mut Vec<Change>) -> io::Result<()> {
+        let old = self.index.remove(relative);
+        let new = self.scanner.scan_file(self.index.root(), relative)?;
+
+        match (old, new) {
+            (Some((old_extension, _)), Some((extension, entry))) if old_extension == extension => {
+                self.index.insert_sorted(extension, entry);
+            }
+            (old, new) => {
+                if let Some((extension, entry)) = old {
+                    changes.push(Change::Removed(extension, entry));
+                }
+                if let Some((extension, entry)) = new {
+                    changes.push(Change::Added(extension.clone(), entry.clone()));
+                    self.index.insert_sorted(extension, entry);
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Every non-directory below `dir`, as paths joined onto `relative`.
+/// Symlinked directories are not followed, as in a scan.
+fn files_under(dir: &Path, relative: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        // Removed again before it could be read.
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(files),
+        Err(e) => return Err(e),
+    };
+    for entry in entries {
+        let entry = entry?;
+        let path = relative.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            files.extend(files_under(&entry.path(), &path)?);
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(files)
+}